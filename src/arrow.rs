@@ -0,0 +1,392 @@
+//! Conversion of parsed records to Arrow `RecordBatch`es
+//!
+//! Records are transposed once into a `ColumnIndex`, then each batch converts
+//! its slice of every column's spans. Numbers, dates and booleans go through
+//! the typed column parsers, whose validity bitmaps are already laid out as
+//! Arrow null buffers. Strings are copied from the field spans straight into
+//! one values buffer with its offsets, without building a `String` per field.
+
+use crate::column::{Bitmap, Column, TypedColumn, MISSING};
+use crate::conventions::ValueConventions;
+use crate::decimal::DecimalFormat;
+use crate::error::{Error, Result};
+use crate::parser::ParsedCsv;
+use crate::schema::{infer_schema_with, DataType, Schema};
+use arrow_array::types::{
+    ArrowPrimitiveType, Date32Type, Decimal128Type, Float64Type, Int64Type,
+    TimestampMicrosecondType, TimestampMillisecondType, TimestampNanosecondType,
+    TimestampSecondType, UInt64Type,
+};
+use arrow_array::{
+    ArrayRef, BooleanArray, GenericStringArray, NullArray, OffsetSizeTrait, PrimitiveArray,
+    RecordBatch, RecordBatchOptions,
+};
+use arrow_buffer::{BooleanBuffer, Buffer, NullBuffer, OffsetBuffer, ScalarBuffer};
+use arrow_schema::{DataType as ArrowType, Field, Schema as ArrowSchema, SchemaRef, TimeUnit};
+use std::sync::Arc;
+
+/// Rows per batch by default
+pub const DEFAULT_BATCH_SIZE: usize = 8192;
+
+/// Options for `to_record_batches`
+#[derive(Clone, Debug)]
+pub struct ArrowOptions {
+    /// Rows per batch; the last batch may be shorter
+    pub batch_size: usize,
+    /// Column types, or `None` to infer them with `infer_schema_with`
+    pub schema: Option<SchemaRef>,
+    /// Infer types from the first `sample` records, or from all with `None`
+    pub sample: Option<usize>,
+    /// Null and boolean tokens
+    pub conventions: ValueConventions,
+    /// Separators of `Decimal128` columns; precision and scale come from
+    /// the column type
+    pub decimal: DecimalFormat,
+}
+
+impl Default for ArrowOptions {
+    fn default() -> Self {
+        Self {
+            batch_size: DEFAULT_BATCH_SIZE,
+            schema: None,
+            sample: None,
+            conventions: ValueConventions::default(),
+            decimal: DecimalFormat::default(),
+        }
+    }
+}
+
+impl Schema {
+    /// Map the inferred types to an Arrow schema
+    ///
+    /// Dates become `Date32` and timestamps `Timestamp(Nanosecond, None)`.
+    pub fn to_arrow(&self) -> ArrowSchema {
+        let fields: Vec<Field> = self
+            .fields
+            .iter()
+            .map(|field| {
+                let data_type = match field.data_type {
+                    DataType::Null => ArrowType::Null,
+                    DataType::Boolean => ArrowType::Boolean,
+                    DataType::Int64 => ArrowType::Int64,
+                    DataType::Float64 => ArrowType::Float64,
+                    DataType::Date => ArrowType::Date32,
+                    DataType::Timestamp => ArrowType::Timestamp(TimeUnit::Nanosecond, None),
+                    DataType::Utf8 => ArrowType::Utf8,
+                };
+                Field::new(&field.name, data_type, field.nullable)
+            })
+            .collect();
+        ArrowSchema::new(fields)
+    }
+}
+
+/// Convert the records of `buf`, which `pcsv` was parsed from, to batches
+/// of at most `options.batch_size` rows
+///
+/// Schema columns are matched to CSV columns by position. Fields that are
+/// null tokens, missing from short records or do not fit the column type
+/// are null; a null in a non-nullable column fails the batch.
+pub fn to_record_batches(
+    pcsv: &ParsedCsv,
+    buf: &[u8],
+    options: &ArrowOptions,
+) -> Result<Vec<RecordBatch>> {
+    let schema = match &options.schema {
+        Some(schema) => schema.clone(),
+        None => {
+            let inferred = infer_schema_with(pcsv, buf, options.sample, &options.conventions);
+            let mut schema = inferred.to_arrow();
+            if options.sample.is_some() {
+                // Records past the sample may hold nulls or other types
+                let fields: Vec<Field> = schema
+                    .fields()
+                    .iter()
+                    .map(|f| f.as_ref().clone().with_nullable(true))
+                    .collect();
+                schema = ArrowSchema::new(fields);
+            }
+            Arc::new(schema)
+        }
+    };
+
+    let index = pcsv.column_index(buf)?;
+    let batch_size = options.batch_size.max(1);
+    let mut batches = Vec::with_capacity(index.rows().div_ceil(batch_size));
+    // Spans for schema columns past the widest record, sized to the rows
+    // actually present rather than to `batch_size`
+    let missing = vec![[MISSING; 2]; batch_size.min(index.rows())];
+
+    for start in (0..index.rows()).step_by(batch_size) {
+        let rows = batch_size.min(index.rows() - start);
+        let mut arrays = Vec::with_capacity(schema.fields().len());
+        for (col, field) in schema.fields().iter().enumerate() {
+            let spans = match index.spans(col) {
+                Some(spans) => &spans[start..start + rows],
+                None => &missing[..rows],
+            };
+            let column = Column::new(buf, spans).with_conventions(&options.conventions);
+            arrays.push(to_array(&column, field, &options.decimal)?);
+        }
+        let batch_options = RecordBatchOptions::new().with_row_count(Some(rows));
+        let batch = RecordBatch::try_new_with_options(schema.clone(), arrays, &batch_options)
+            .map_err(|e| Error::Arrow {
+                message: e.to_string(),
+            })?;
+        batches.push(batch);
+    }
+    Ok(batches)
+}
+
+impl ParsedCsv {
+    /// Convert the records of `buf` to Arrow `RecordBatch`es
+    pub fn record_batches(&self, buf: &[u8], options: &ArrowOptions) -> Result<Vec<RecordBatch>> {
+        to_record_batches(self, buf, options)
+    }
+}
+
+/// Convert one batch of a column to an array of the field's type
+fn to_array(column: &Column, field: &Field, decimal: &DecimalFormat) -> Result<ArrayRef> {
+    let array: ArrayRef = match field.data_type() {
+        ArrowType::Null => Arc::new(NullArray::new(column.len())),
+        ArrowType::Boolean => {
+            let TypedColumn {
+                values, validity, ..
+            } = column.parse_bool();
+            Arc::new(BooleanArray::new(
+                BooleanBuffer::from(values),
+                null_buffer(validity),
+            ))
+        }
+        ArrowType::Int64 => Arc::new(primitive::<Int64Type, _>(column.parse_i64())),
+        ArrowType::UInt64 => Arc::new(primitive::<UInt64Type, _>(column.parse_u64())),
+        ArrowType::Float64 => Arc::new(primitive::<Float64Type, _>(column.parse_f64())),
+        ArrowType::Date32 => Arc::new(primitive::<Date32Type, _>(column.parse_date())),
+        ArrowType::Timestamp(unit, tz) => {
+            let mut stamps = column.parse_timestamp();
+            let per_unit = match unit {
+                TimeUnit::Second => 1_000_000_000,
+                TimeUnit::Millisecond => 1_000_000,
+                TimeUnit::Microsecond => 1_000,
+                TimeUnit::Nanosecond => 1,
+            };
+            if per_unit > 1 {
+                for value in &mut stamps.values {
+                    *value = value.div_euclid(per_unit);
+                }
+            }
+            match unit {
+                TimeUnit::Second => Arc::new(
+                    primitive::<TimestampSecondType, _>(stamps).with_timezone_opt(tz.clone()),
+                ),
+                TimeUnit::Millisecond => Arc::new(
+                    primitive::<TimestampMillisecondType, _>(stamps).with_timezone_opt(tz.clone()),
+                ),
+                TimeUnit::Microsecond => Arc::new(
+                    primitive::<TimestampMicrosecondType, _>(stamps).with_timezone_opt(tz.clone()),
+                ),
+                TimeUnit::Nanosecond => Arc::new(
+                    primitive::<TimestampNanosecondType, _>(stamps).with_timezone_opt(tz.clone()),
+                ),
+            }
+        }
+        &ArrowType::Decimal128(precision, scale) if scale >= 0 => {
+            let format = DecimalFormat {
+                precision: precision as u32,
+                scale: scale as u32,
+                ..decimal.clone()
+            };
+            let array = primitive::<Decimal128Type, _>(column.parse_decimal(&format))
+                .with_precision_and_scale(precision, scale)
+                .map_err(|e| Error::Arrow {
+                    message: e.to_string(),
+                })?;
+            Arc::new(array)
+        }
+        ArrowType::Utf8 => Arc::new(string_array::<i32>(column, field)?),
+        ArrowType::LargeUtf8 => Arc::new(string_array::<i64>(column, field)?),
+        other => {
+            return Err(Error::Arrow {
+                message: format!("column '{}' has unsupported type {}", field.name(), other),
+            })
+        }
+    };
+    Ok(array)
+}
+
+fn null_buffer(validity: Bitmap) -> Option<NullBuffer> {
+    let len = validity.len();
+    Some(NullBuffer::new(BooleanBuffer::new(
+        Buffer::from_vec(validity.into_bytes()),
+        0,
+        len,
+    )))
+}
+
+fn primitive<T: ArrowPrimitiveType, E>(column: TypedColumn<T::Native, E>) -> PrimitiveArray<T> {
+    PrimitiveArray::new(
+        ScalarBuffer::from(column.values),
+        null_buffer(column.validity),
+    )
+}
+
+/// Copy the fields of `column` into one values buffer with offsets
+///
+/// Null tokens, missing fields and fields that are not UTF-8 are null.
+fn string_array<O: OffsetSizeTrait>(
+    column: &Column,
+    field: &Field,
+) -> Result<GenericStringArray<O>> {
+    let mut validity = Bitmap::new(column.len());
+    let mut offsets = Vec::with_capacity(column.len() + 1);
+    let mut values = Vec::new();
+    offsets.push(O::zero());
+    for row in 0..column.len() {
+        if let Some(value) = column.get_unquoted(row) {
+            if !column.is_null(&value) && std::str::from_utf8(&value).is_ok() {
+                values.extend_from_slice(&value);
+                validity.set(row);
+            }
+        }
+        offsets.push(O::from_usize(values.len()).ok_or_else(|| Error::Arrow {
+            message: format!(
+                "column '{}' holds more string data than its offsets can address",
+                field.name()
+            ),
+        })?);
+    }
+    let offsets = OffsetBuffer::new(ScalarBuffer::from(offsets));
+    // SAFETY: every field appended to `values` was checked to be UTF-8, so
+    // each offset falls on a character boundary
+    Ok(unsafe {
+        GenericStringArray::new_unchecked(offsets, Buffer::from_vec(values), null_buffer(validity))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::memory::Padded;
+    use crate::parser::{parse_csv_with, ParseOptions};
+    use arrow_array::{Array, Decimal128Array, Float64Array, Int64Array, StringArray};
+
+    fn parse(data: &[u8]) -> ParsedCsv {
+        let options = ParseOptions {
+            has_header: true,
+            ..ParseOptions::default()
+        };
+        parse_csv_with(Padded::from_slice(data).as_padded(), &options).unwrap()
+    }
+
+    #[test]
+    fn test_inferred_batches() {
+        let data = b"id,name,score,day\n1,\"a, b\",1.5,2012-08-16\n2,,x,\n3,c\n4,\"d\"\"\",2,2012-08-17\n5,e,3,2012-08-18\n";
+        let pcsv = parse(data);
+        let options = ArrowOptions {
+            batch_size: 2,
+            ..ArrowOptions::default()
+        };
+        let batches = pcsv.record_batches(data, &options).unwrap();
+        assert_eq!(
+            batches.iter().map(|b| b.num_rows()).collect::<Vec<_>>(),
+            [2, 2, 1]
+        );
+
+        let schema = batches[0].schema();
+        let types: Vec<&ArrowType> = schema.fields().iter().map(|f| f.data_type()).collect();
+        assert_eq!(
+            types,
+            [
+                &ArrowType::Int64,
+                &ArrowType::Utf8,
+                &ArrowType::Utf8,
+                &ArrowType::Date32
+            ]
+        );
+        assert!(!schema.field(0).is_nullable());
+
+        let ids = batches[1]
+            .column(0)
+            .as_any()
+            .downcast_ref::<Int64Array>()
+            .unwrap();
+        assert_eq!(ids.values(), &[3, 4]);
+        let names = batches[0]
+            .column(1)
+            .as_any()
+            .downcast_ref::<StringArray>()
+            .unwrap();
+        assert_eq!(names.value(0), "a, b");
+        assert!(names.is_null(1));
+        let names = batches[1]
+            .column(1)
+            .as_any()
+            .downcast_ref::<StringArray>()
+            .unwrap();
+        assert_eq!((names.value(0), names.value(1)), ("c", "d\""));
+        assert_eq!(names.value_offsets(), &[0, 1, 3]);
+        assert!(batches[1].column(3).is_null(0));
+    }
+
+    #[test]
+    fn test_given_schema() {
+        let data = b"amount,rate,flag\n\"1.234,50\",\"0,5\",x\n-7,oops,y\n";
+        let pcsv = parse(data);
+        let schema = ArrowSchema::new(vec![
+            Field::new("amount", ArrowType::Decimal128(10, 2), false),
+            Field::new("rate", ArrowType::Float64, true),
+            Field::new("flag", ArrowType::LargeUtf8, true),
+            Field::new("extra", ArrowType::Int64, true),
+        ]);
+        let options = ArrowOptions {
+            schema: Some(Arc::new(schema)),
+            decimal: DecimalFormat {
+                decimal: b',',
+                thousands: Some(b'.'),
+                ..DecimalFormat::default()
+            },
+            ..ArrowOptions::default()
+        };
+        let batches = to_record_batches(&pcsv, data, &options).unwrap();
+        assert_eq!(batches.len(), 1);
+        let batch = &batches[0];
+
+        let amounts = batch
+            .column(0)
+            .as_any()
+            .downcast_ref::<Decimal128Array>()
+            .unwrap();
+        assert_eq!(amounts.values(), &[123_450, -700]);
+        assert_eq!(amounts.value_as_string(0), "1234.50");
+        let rates = batch
+            .column(1)
+            .as_any()
+            .downcast_ref::<Float64Array>()
+            .unwrap();
+        assert!(rates.is_null(0) && rates.is_null(1));
+        assert_eq!(batch.column(2).len(), 2);
+        assert_eq!(batch.column(3).null_count(), 2);
+
+        // One batch for everything, without allocating for `batch_size` rows
+        let whole = ArrowOptions {
+            batch_size: usize::MAX,
+            ..options.clone()
+        };
+        let batches = to_record_batches(&pcsv, data, &whole).unwrap();
+        assert_eq!(batches.len(), 1);
+        assert_eq!(batches[0].column(3).null_count(), 2);
+
+        let strict = ArrowOptions {
+            schema: Some(Arc::new(ArrowSchema::new(vec![Field::new(
+                "rate",
+                ArrowType::Int64,
+                false,
+            )]))),
+            ..ArrowOptions::default()
+        };
+        assert!(matches!(
+            to_record_batches(&pcsv, data, &strict),
+            Err(Error::Arrow { .. })
+        ));
+    }
+}
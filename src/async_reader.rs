@@ -0,0 +1,416 @@
+//! Asynchronous streaming reader over `tokio::io::AsyncRead`
+//!
+//! Input is read in chunks and scanned with the resumable SIMD parser, so
+//! each byte is scanned once no matter how records straddle chunk
+//! boundaries. Complete records are handed out in batches.
+
+use crate::error::{Error, ParseErrorKind, Result};
+use crate::limits::{max_indexes, Limits};
+use crate::memory::Padded;
+use crate::parser::{find_indexes_resumable, ParsedCsv, ParserState};
+use crate::record::Records;
+use crate::CSV_PADDING;
+use futures_core::Stream;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use tokio::io::{AsyncRead, ReadBuf};
+
+/// Default number of records per batch
+pub const DEFAULT_BATCH_RECORDS: usize = 1024;
+
+/// Default number of bytes requested from the reader at a time
+pub const DEFAULT_CHUNK_SIZE: usize = 64 * 1024;
+
+/// A batch of complete records owned by the caller
+pub struct CsvBatch {
+    data: Padded<Vec<u8>>,
+    parsed: ParsedCsv,
+    records: usize,
+}
+
+impl CsvBatch {
+    /// Get the raw bytes of the records in this batch
+    pub fn data(&self) -> &[u8] {
+        self.data.data()
+    }
+
+    /// Get the separator indexes, relative to the start of `data()`
+    pub fn parsed(&self) -> &ParsedCsv {
+        &self.parsed
+    }
+
+    /// Iterate over the records in this batch
+    pub fn records(&self) -> Records<'_> {
+        self.parsed.records(self.data.data())
+    }
+
+    /// Get the number of records in this batch
+    pub fn len(&self) -> usize {
+        self.records
+    }
+
+    /// Check if the batch holds no records
+    pub fn is_empty(&self) -> bool {
+        self.records == 0
+    }
+}
+
+/// Streaming CSV reader yielding batches of records
+pub struct AsyncCsvReader<R> {
+    reader: R,
+    /// Unconsumed input followed by `CSV_PADDING` zero bytes
+    buf: Vec<u8>,
+    len: usize,
+    scanned: usize,
+    /// Bytes of input already handed out in batches
+    consumed: usize,
+    state: ParserState,
+    pending: ParsedCsv,
+    /// Number of pending indexes already checked for record terminators
+    counted: usize,
+    terminators: usize,
+    batch_records: usize,
+    chunk_size: usize,
+    eof: bool,
+    /// Set after a parse or limit error, which ends the stream
+    failed: bool,
+}
+
+impl<R: AsyncRead + Unpin> AsyncCsvReader<R> {
+    /// Create a reader with the default batch and chunk sizes
+    pub fn new(reader: R) -> Self {
+        Self::with_sizes(reader, DEFAULT_BATCH_RECORDS, DEFAULT_CHUNK_SIZE)
+    }
+
+    /// Create a reader yielding `batch_records` records per batch and
+    /// reading `chunk_size` bytes at a time
+    pub fn with_sizes(reader: R, batch_records: usize, chunk_size: usize) -> Self {
+        Self::with_limits(reader, batch_records, chunk_size, &Limits::default())
+    }
+
+    /// Create a reader like `with_sizes` that enforces `limits` on the whole
+    /// stream; `max_index_bytes` bounds the indexes not yet handed out
+    pub fn with_limits(
+        reader: R,
+        batch_records: usize,
+        chunk_size: usize,
+        limits: &Limits,
+    ) -> Self {
+        let mut pending = ParsedCsv::with_capacity((chunk_size / 10).min(max_indexes(limits)));
+        pending.limits = limits.clone();
+        Self {
+            reader,
+            buf: vec![0; CSV_PADDING],
+            len: 0,
+            scanned: 0,
+            consumed: 0,
+            state: ParserState::with_limits(limits),
+            pending,
+            counted: 0,
+            terminators: 0,
+            batch_records: batch_records.max(1),
+            chunk_size: chunk_size.max(1),
+            eof: false,
+            failed: false,
+        }
+    }
+
+    /// Read the next batch, or `None` once the input is exhausted
+    pub async fn next_batch(&mut self) -> Option<Result<CsvBatch>> {
+        std::future::poll_fn(|cx| Pin::new(&mut *self).poll_next(cx)).await
+    }
+
+    /// Count record terminators among newly scanned indexes, returning the
+    /// number of indexes that make up `batch_records` complete records
+    fn find_cut(&mut self) -> Option<usize> {
+        while self.counted < self.pending.indexes.len() {
+            let idx = self.pending.indexes[self.counted] as usize;
+            self.counted += 1;
+            if self.buf[idx] == b'\n' {
+                self.terminators += 1;
+                if self.terminators == self.batch_records {
+                    return Some(self.counted);
+                }
+            }
+        }
+        None
+    }
+
+    /// Move the first `cut_indexes` indexes and the bytes they cover into a batch
+    fn take_batch(&mut self, cut_indexes: usize, records: usize) -> CsvBatch {
+        let cut_bytes = if cut_indexes == self.pending.indexes.len() && self.eof {
+            self.len
+        } else {
+            self.pending.indexes[cut_indexes - 1] as usize + 1
+        };
+
+        let data = Padded::from_slice(&self.buf[..cut_bytes]);
+        let mut parsed = ParsedCsv::with_capacity(cut_indexes);
+        parsed
+            .indexes
+            .extend_from_slice(&self.pending.indexes[..cut_indexes]);
+
+        // Shift the remainder, padding included, to the front
+        self.buf.copy_within(cut_bytes..self.len + CSV_PADDING, 0);
+        self.len -= cut_bytes;
+        self.consumed += cut_bytes;
+        self.buf.truncate(self.len + CSV_PADDING);
+        self.scanned -= cut_bytes;
+        self.state.limits.rebase(cut_bytes);
+        self.pending.indexes.drain(..cut_indexes);
+        for idx in &mut self.pending.indexes {
+            *idx -= cut_bytes as u32;
+        }
+        self.counted = 0;
+        self.terminators = 0;
+
+        CsvBatch {
+            data,
+            parsed,
+            records,
+        }
+    }
+
+    /// Emit everything left once the reader is exhausted
+    ///
+    /// Input ending inside a quoted field is reported as an error and the
+    /// open record is discarded.
+    fn take_rest(&mut self) -> Option<Result<CsvBatch>> {
+        if self.len == 0 {
+            return None;
+        }
+        if self.state.inside_quote() {
+            let offset = self.consumed
+                + self
+                    .pending
+                    .indexes
+                    .last()
+                    .map_or(0, |&idx| idx as usize + 1);
+            self.fail();
+            return Some(Err(Error::Parse {
+                offset,
+                kind: ParseErrorKind::UnterminatedQuote,
+            }));
+        }
+        if let Some(err) = self.state.limits.finish(self.len) {
+            let err = self.with_stream_offset(err);
+            self.fail();
+            return Some(Err(err));
+        }
+        // The last record may lack a terminator
+        let records = self.terminators + usize::from(self.buf[self.len - 1] != b'\n');
+        let cut_indexes = self.pending.indexes.len();
+        if cut_indexes == 0 {
+            let data = Padded::from_slice(&self.buf[..self.len]);
+            self.len = 0;
+            self.scanned = 0;
+            self.buf.truncate(CSV_PADDING);
+            self.buf.fill(0);
+            return Some(Ok(CsvBatch {
+                data,
+                parsed: ParsedCsv::with_capacity(0),
+                records,
+            }));
+        }
+        Some(Ok(self.take_batch(cut_indexes, records)))
+    }
+
+    /// Scan bytes appended since the last scan
+    fn scan(&mut self) -> Result<()> {
+        let padded = Padded::new(&self.buf, self.len)?;
+        find_indexes_resumable(padded, self.scanned, &mut self.pending, &mut self.state)
+            .map_err(|err| self.with_stream_offset(err))?;
+        self.scanned = self.len;
+        Ok(())
+    }
+
+    /// End the stream, dropping the input and indexes not handed out
+    ///
+    /// A failed scan leaves the indexes and limit counts partway through
+    /// the input, so nothing after an error can be trusted.
+    fn fail(&mut self) {
+        self.failed = true;
+        self.len = 0;
+        self.scanned = 0;
+        self.buf.clear();
+        self.buf.resize(CSV_PADDING, 0);
+        self.pending.indexes.clear();
+        self.counted = 0;
+        self.terminators = 0;
+    }
+
+    /// Turn the buffer offset of a limit error into a stream offset
+    fn with_stream_offset(&self, err: Error) -> Error {
+        match err {
+            Error::LimitExceeded { limit, max, offset } => Error::LimitExceeded {
+                limit,
+                max,
+                offset: self.consumed + offset,
+            },
+            err => err,
+        }
+    }
+}
+
+impl<R: AsyncRead + Unpin> Stream for AsyncCsvReader<R> {
+    type Item = Result<CsvBatch>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let this = self.get_mut();
+        if this.failed {
+            return Poll::Ready(None);
+        }
+
+        loop {
+            if let Some(cut) = this.find_cut() {
+                let records = this.batch_records;
+                return Poll::Ready(Some(Ok(this.take_batch(cut, records))));
+            }
+            if this.eof {
+                return Poll::Ready(this.take_rest());
+            }
+
+            // Grow the buffer by one chunk, keeping everything past the data zeroed
+            this.buf.truncate(this.len);
+            this.buf.resize(this.len + this.chunk_size + CSV_PADDING, 0);
+
+            let mut read_buf = ReadBuf::new(&mut this.buf[this.len..this.len + this.chunk_size]);
+            let n = match Pin::new(&mut this.reader).poll_read(cx, &mut read_buf) {
+                Poll::Ready(Ok(())) => read_buf.filled().len(),
+                Poll::Ready(Err(e)) => {
+                    this.buf.truncate(this.len + CSV_PADDING);
+                    this.buf[this.len..].fill(0);
+                    return Poll::Ready(Some(Err(Error::io("could not read input", e))));
+                }
+                Poll::Pending => {
+                    this.buf.truncate(this.len + CSV_PADDING);
+                    return Poll::Pending;
+                }
+            };
+
+            this.len += n;
+            this.buf.truncate(this.len + CSV_PADDING);
+            if n == 0 {
+                this.eof = true;
+            } else if let Err(e) = this.scan() {
+                this.fail();
+                return Poll::Ready(Some(Err(e)));
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::Limit;
+
+    async fn collect(data: &[u8], batch_records: usize, chunk_size: usize) -> Vec<Vec<Vec<u8>>> {
+        let mut reader = AsyncCsvReader::with_sizes(data, batch_records, chunk_size);
+        let mut batches = Vec::new();
+        while let Some(batch) = reader.next_batch().await {
+            let batch = batch.unwrap();
+            let records: Vec<Vec<u8>> = batch
+                .records()
+                .map(|r| r.iter().collect::<Vec<_>>().join(&b'|'))
+                .collect();
+            assert_eq!(records.len(), batch.len());
+            batches.push(records);
+        }
+        batches
+    }
+
+    #[tokio::test]
+    async fn test_batches_across_chunks() {
+        let mut data = Vec::new();
+        for i in 0..25 {
+            data.extend_from_slice(format!("{},\"q,{}\nz\",end\n", i, i).as_bytes());
+        }
+
+        for chunk_size in [1, 7, 64, 100, 4096] {
+            let batches = collect(&data, 10, chunk_size).await;
+            assert_eq!(
+                batches.iter().map(|b| b.len()).collect::<Vec<_>>(),
+                vec![10, 10, 5],
+                "chunk size {}",
+                chunk_size
+            );
+            assert_eq!(batches[2][4], b"24|\"q,24\nz\"|end".to_vec());
+        }
+    }
+
+    #[tokio::test]
+    async fn test_unterminated_last_record() {
+        let batches = collect(b"a,b\nc,d", 10, 3).await;
+        assert_eq!(batches, vec![vec![b"a|b".to_vec(), b"c|d".to_vec()]]);
+
+        let batches = collect(b"", 10, 3).await;
+        assert!(batches.is_empty());
+
+        let batches = collect(b"solo", 10, 3).await;
+        assert_eq!(batches, vec![vec![b"solo".to_vec()]]);
+    }
+
+    #[tokio::test]
+    async fn test_unterminated_quote() {
+        let data: &[u8] = b"a,b\nc,\"open\nd,e\n";
+        let mut reader = AsyncCsvReader::with_sizes(data, 1, 4);
+
+        let first = reader.next_batch().await.unwrap().unwrap();
+        assert_eq!(first.len(), 1);
+        let err = reader.next_batch().await.unwrap().err().unwrap();
+        assert!(matches!(
+            err,
+            Error::Parse {
+                offset: 6,
+                kind: ParseErrorKind::UnterminatedQuote
+            }
+        ));
+        assert!(reader.next_batch().await.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_limits_span_batches() {
+        let data = b"a,b\n".repeat(30);
+        let limits = Limits {
+            max_records: Some(25),
+            ..Limits::default()
+        };
+        let mut reader = AsyncCsvReader::with_limits(&data[..], 10, 16, &limits);
+        for _ in 0..2 {
+            assert_eq!(reader.next_batch().await.unwrap().unwrap().len(), 10);
+        }
+        let err = reader.next_batch().await.unwrap().err().unwrap();
+        assert!(matches!(
+            err,
+            Error::LimitExceeded {
+                limit: Limit::Records,
+                max: 25,
+                offset: 100
+            }
+        ));
+        // The stream ends at the error instead of rescanning stale input
+        assert!(reader.next_batch().await.is_none());
+        assert!(reader.next_batch().await.is_none());
+
+        // A quote that never closes is stopped by the field length limit
+        let mut data = b"id\n\"".to_vec();
+        data.extend(vec![b'x'; 10_000]);
+        let limits = Limits {
+            max_field_len: Some(1000),
+            ..Limits::default()
+        };
+        let mut reader = AsyncCsvReader::with_limits(&data[..], 1, 64, &limits);
+        assert_eq!(reader.next_batch().await.unwrap().unwrap().len(), 1);
+        let err = reader.next_batch().await.unwrap().err().unwrap();
+        assert!(matches!(
+            err,
+            Error::LimitExceeded {
+                limit: Limit::FieldLength,
+                offset: 3,
+                ..
+            }
+        ));
+        assert!(reader.next_batch().await.is_none());
+    }
+}
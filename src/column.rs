@@ -0,0 +1,444 @@
+//! Typed access to a single column
+//!
+//! A `Column` is the list of field spans of one column across the records.
+//! The `parse_*` methods convert all of them at once into a value vector
+//! with a validity bitmap, collecting a per-row error for every field that
+//! does not convert.
+
+use crate::conventions::{Classifier, InvalidBool, ValueConventions};
+use crate::datetime::{
+    iso_kernel, parse_date_with, parse_timestamp_with, DatePattern, DateTimeError,
+};
+use crate::decimal::{DecimalError, DecimalFormat};
+use crate::float::parse_f64_with;
+use crate::numeric::{digits_kernel, parse_i64_with, parse_u64_with, NumberError};
+use crate::parser::ParsedCsv;
+use crate::projection::ProjectedCsv;
+use crate::record::unquote;
+use std::borrow::Cow;
+
+/// Marks a field missing from a short record
+pub(crate) const MISSING: u32 = u32::MAX;
+
+/// Bit-packed validity flags, least significant bit first
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Bitmap {
+    bytes: Vec<u8>,
+    len: usize,
+}
+
+impl Bitmap {
+    /// Create a bitmap of `len` cleared bits
+    pub fn new(len: usize) -> Self {
+        Self {
+            bytes: vec![0; len.div_ceil(8)],
+            len,
+        }
+    }
+
+    /// Get the number of bits
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Check if the bitmap holds no bits
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Get bit `i`
+    #[inline]
+    pub fn get(&self, i: usize) -> bool {
+        i < self.len && self.bytes[i / 8] & (1 << (i % 8)) != 0
+    }
+
+    /// Set bit `i`
+    #[inline]
+    pub fn set(&mut self, i: usize) {
+        self.bytes[i / 8] |= 1 << (i % 8);
+    }
+
+    /// Count the set bits
+    pub fn count_set(&self) -> usize {
+        self.bytes.iter().map(|b| b.count_ones() as usize).sum()
+    }
+
+    /// Get the packed bytes
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.bytes
+    }
+
+    /// Take the packed bytes, laid out like an Arrow validity buffer
+    pub fn into_bytes(self) -> Vec<u8> {
+        self.bytes
+    }
+}
+
+/// A field that could not be converted
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct FieldError<E> {
+    /// 0-based row within the column
+    pub row: usize,
+    pub error: E,
+}
+
+/// Converted values of a column
+///
+/// Rows that are null, missing or failed to convert hold `T::default()`
+/// and a cleared validity bit.
+#[derive(Clone, Debug, Default)]
+pub struct TypedColumn<T, E> {
+    pub values: Vec<T>,
+    pub validity: Bitmap,
+    pub errors: Vec<FieldError<E>>,
+}
+
+impl<T, E> TypedColumn<T, E> {
+    /// Get the value of `row`, if it is valid
+    pub fn get(&self, row: usize) -> Option<&T> {
+        self.validity.get(row).then(|| &self.values[row])
+    }
+
+    /// Get the number of rows
+    pub fn len(&self) -> usize {
+        self.values.len()
+    }
+
+    /// Check if the column has no rows
+    pub fn is_empty(&self) -> bool {
+        self.values.is_empty()
+    }
+}
+
+/// The fields of one column across records
+#[derive(Clone, Debug)]
+pub struct Column<'a> {
+    buf: &'a [u8],
+    spans: Cow<'a, [[u32; 2]]>,
+    classifier: Classifier,
+}
+
+impl<'a> Column<'a> {
+    pub(crate) fn new(buf: &'a [u8], spans: impl Into<Cow<'a, [[u32; 2]]>>) -> Self {
+        Self {
+            buf,
+            spans: spans.into(),
+            classifier: Classifier::default(),
+        }
+    }
+
+    /// Classify null and boolean fields with `conventions`
+    pub fn with_conventions(mut self, conventions: &ValueConventions) -> Self {
+        self.classifier = Classifier::new(conventions);
+        self
+    }
+
+    /// Get the number of rows
+    pub fn len(&self) -> usize {
+        self.spans.len()
+    }
+
+    /// Check if the column has no rows
+    pub fn is_empty(&self) -> bool {
+        self.spans.is_empty()
+    }
+
+    /// Get the raw bytes of `row`, or `None` if the record is too short
+    pub fn get(&self, row: usize) -> Option<&'a [u8]> {
+        let [start, end] = *self.spans.get(row)?;
+        (start != MISSING).then(|| &self.buf[start as usize..end as usize])
+    }
+
+    /// Get the field of `row` with its quotes removed
+    pub fn get_unquoted(&self, row: usize) -> Option<Cow<'a, [u8]>> {
+        self.get(row).map(unquote)
+    }
+
+    /// Check if `field` is empty or one of the null tokens
+    pub(crate) fn is_null(&self, field: &[u8]) -> bool {
+        self.classifier.is_null(field)
+    }
+
+    /// Convert every field with `parse`; missing fields and null tokens are null
+    pub(crate) fn parse_with<T: Default, E>(
+        &self,
+        parse: impl Fn(&[u8]) -> Result<T, E>,
+    ) -> TypedColumn<T, E> {
+        let mut out = TypedColumn {
+            values: Vec::with_capacity(self.len()),
+            validity: Bitmap::new(self.len()),
+            errors: Vec::new(),
+        };
+        for row in 0..self.len() {
+            let field = self.get_unquoted(row).unwrap_or_default();
+            if self.is_null(&field) {
+                out.values.push(T::default());
+                continue;
+            }
+            match parse(&field) {
+                Ok(value) => {
+                    out.values.push(value);
+                    out.validity.set(row);
+                }
+                Err(error) => {
+                    out.values.push(T::default());
+                    out.errors.push(FieldError { row, error });
+                }
+            }
+        }
+        out
+    }
+
+    /// Convert every true or false token to `bool`
+    pub fn parse_bool(&self) -> TypedColumn<bool, InvalidBool> {
+        self.parse_with(|field| self.classifier.parse_bool(field))
+    }
+
+    /// Convert every field to `i64`
+    pub fn parse_i64(&self) -> TypedColumn<i64, NumberError> {
+        let kernel = digits_kernel();
+        self.parse_with(|field| parse_i64_with(field, kernel))
+    }
+
+    /// Convert every field to `u64`
+    pub fn parse_u64(&self) -> TypedColumn<u64, NumberError> {
+        let kernel = digits_kernel();
+        self.parse_with(|field| parse_u64_with(field, kernel))
+    }
+
+    /// Convert every field to `f64` with `.` as the decimal separator
+    pub fn parse_f64(&self) -> TypedColumn<f64, NumberError> {
+        self.parse_f64_with(b'.')
+    }
+
+    /// Convert every field to `f64` with `decimal` as the decimal separator
+    pub fn parse_f64_with(&self, decimal: u8) -> TypedColumn<f64, NumberError> {
+        self.parse_with(|field| parse_f64_with(field, decimal))
+    }
+
+    /// Convert every field to an `i128` mantissa at `format.scale`
+    pub fn parse_decimal(&self, format: &DecimalFormat) -> TypedColumn<i128, DecimalError> {
+        self.parse_with(|field| format.parse(field))
+    }
+
+    /// Convert every ISO 8601 date to days since 1970-01-01
+    pub fn parse_date(&self) -> TypedColumn<i32, DateTimeError> {
+        let kernel = iso_kernel();
+        self.parse_with(|field| parse_date_with(field, kernel))
+    }
+
+    /// Convert every ISO 8601 timestamp to nanoseconds since the epoch
+    pub fn parse_timestamp(&self) -> TypedColumn<i64, DateTimeError> {
+        let kernel = iso_kernel();
+        self.parse_with(|field| parse_timestamp_with(field, kernel))
+    }
+
+    /// Convert every field laid out as `pattern` to days since 1970-01-01
+    pub fn parse_date_with(&self, pattern: &DatePattern) -> TypedColumn<i32, DateTimeError> {
+        self.parse_with(|field| pattern.parse_date(field))
+    }
+
+    /// Convert every field laid out as `pattern` to nanoseconds since the epoch
+    pub fn parse_timestamp_with(&self, pattern: &DatePattern) -> TypedColumn<i64, DateTimeError> {
+        self.parse_with(|field| pattern.parse_timestamp(field))
+    }
+}
+
+impl ParsedCsv {
+    /// Get column `index` of `buf`, one row per record
+    pub fn column<'a>(&'a self, buf: &'a [u8], index: usize) -> Column<'a> {
+        let spans: Vec<[u32; 2]> = self
+            .records(buf)
+            .map(|record| match record.field_range(index) {
+                Some(range) => [range.start as u32, range.end as u32],
+                None => [MISSING; 2],
+            })
+            .collect();
+        Column::new(buf, spans)
+    }
+}
+
+impl ProjectedCsv {
+    /// Get selected column `col` of `buf`
+    pub fn column<'a>(&self, buf: &'a [u8], col: usize) -> Column<'a> {
+        let spans: Vec<[u32; 2]> = (0..self.len())
+            .map(|row| match self.span(row, col) {
+                Some(range) => [range.start as u32, range.end as u32],
+                None => [MISSING; 2],
+            })
+            .collect();
+        Column::new(buf, spans)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::memory::Padded;
+    use crate::numeric::NumberError;
+    use crate::parser::{parse_csv_with, ParseOptions};
+
+    #[test]
+    fn test_parse_int_column() {
+        let data = b"id,n\n1,42\n2,-7\n3,\n4,x1\n5,\"9\"\n6,99999999999999999999\n7";
+        let options = ParseOptions {
+            has_header: true,
+            ..ParseOptions::default()
+        };
+        let pcsv = parse_csv_with(Padded::from_slice(data).as_padded(), &options).unwrap();
+
+        let column = pcsv.column(data, 1);
+        assert_eq!(column.len(), 7);
+        let ints = column.parse_i64();
+        assert_eq!(ints.values, [42, -7, 0, 0, 9, 0, 0]);
+        assert_eq!(ints.get(1), Some(&-7));
+        assert_eq!(ints.get(2), None);
+        assert_eq!(ints.validity.count_set(), 3);
+        assert_eq!(
+            ints.errors,
+            [
+                FieldError {
+                    row: 3,
+                    error: NumberError::InvalidDigit
+                },
+                FieldError {
+                    row: 5,
+                    error: NumberError::Overflow
+                },
+            ]
+        );
+
+        let ids = pcsv.column(data, 0).parse_u64();
+        assert_eq!(ids.values, [1, 2, 3, 4, 5, 6, 7]);
+        assert!(ids.errors.is_empty());
+    }
+
+    #[test]
+    fn test_parse_float_column() {
+        let data = b"x,y\n\"1,5\",2\n\"-0,25\",\n1e3,abc\n,1.5";
+        let options = ParseOptions {
+            has_header: true,
+            ..ParseOptions::default()
+        };
+        let pcsv = parse_csv_with(Padded::from_slice(data).as_padded(), &options).unwrap();
+
+        let x = pcsv.column(data, 0).parse_f64_with(b',');
+        assert_eq!(x.values, [1.5, -0.25, 1000.0, 0.0]);
+        assert_eq!(x.validity.count_set(), 3);
+        assert!(x.errors.is_empty());
+
+        let y = pcsv.column(data, 1).parse_f64();
+        assert_eq!(y.values, [2.0, 0.0, 0.0, 1.5]);
+        assert_eq!(
+            y.errors,
+            [FieldError {
+                row: 2,
+                error: NumberError::InvalidDigit
+            }]
+        );
+    }
+
+    #[test]
+    fn test_parse_decimal_column() {
+        let data = b"amount\n\"1.234,50\"\n\"-0,99\"\n\n\"12,345\"\n\"99.999.999,00\"\n";
+        let options = ParseOptions {
+            has_header: true,
+            ..ParseOptions::default()
+        };
+        let pcsv = parse_csv_with(Padded::from_slice(data).as_padded(), &options).unwrap();
+        let format = DecimalFormat {
+            precision: 9,
+            scale: 2,
+            decimal: b',',
+            thousands: Some(b'.'),
+        };
+        let amounts = pcsv.column(data, 0).parse_decimal(&format);
+        assert_eq!(amounts.values, [123_450, -99, 0, 0, 0]);
+        assert_eq!(amounts.validity.count_set(), 2);
+        assert_eq!(
+            amounts.errors,
+            [
+                FieldError {
+                    row: 3,
+                    error: DecimalError::Rounding
+                },
+                FieldError {
+                    row: 4,
+                    error: DecimalError::Overflow
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_parse_date_columns() {
+        let data = b"2012-08-16,16-Aug-2012,2012-11-27 00:16:56\n2012-02-30,3-Jan-2012,x\n";
+        let pcsv = parse_csv_with(
+            Padded::from_slice(data).as_padded(),
+            &ParseOptions::default(),
+        )
+        .unwrap();
+
+        let iso = pcsv.column(data, 0).parse_date();
+        assert_eq!(iso.values, [15568, 0]);
+        assert_eq!(iso.errors[0].error, DateTimeError::OutOfRange);
+
+        let pattern = DatePattern::new("%d-%b-%Y").unwrap();
+        let named = pcsv.column(data, 1).parse_date_with(&pattern);
+        assert_eq!(named.values, [15568, 15342]);
+
+        let stamps = pcsv.column(data, 2).parse_timestamp();
+        assert_eq!(stamps.values[0], 1_353_975_416_000_000_000);
+        assert_eq!(stamps.errors[0].row, 1);
+    }
+
+    #[test]
+    fn test_conventions() {
+        let data = b"NA,Y\n\\N,n\n3,\nnull,x\n";
+        let pcsv = parse_csv_with(
+            Padded::from_slice(data).as_padded(),
+            &ParseOptions::default(),
+        )
+        .unwrap();
+        let conventions = ValueConventions {
+            nulls: vec!["NA".to_string(), "\\N".to_string(), "NULL".to_string()],
+            trues: vec!["Y".to_string()],
+            falses: vec!["N".to_string()],
+            ignore_case: true,
+        };
+
+        let ints = pcsv
+            .column(data, 0)
+            .with_conventions(&conventions)
+            .parse_i64();
+        assert_eq!(ints.values, [0, 0, 3, 0]);
+        assert_eq!(ints.validity.count_set(), 1);
+        assert!(ints.errors.is_empty());
+        assert_eq!(pcsv.column(data, 0).parse_i64().errors.len(), 3);
+
+        let flags = pcsv
+            .column(data, 1)
+            .with_conventions(&conventions)
+            .parse_bool();
+        assert_eq!(flags.values, [true, false, false, false]);
+        assert_eq!(flags.validity.count_set(), 2);
+        assert_eq!(
+            flags.errors,
+            [FieldError {
+                row: 3,
+                error: InvalidBool
+            }]
+        );
+    }
+
+    #[test]
+    fn test_bitmap() {
+        let mut bitmap = Bitmap::new(10);
+        bitmap.set(0);
+        bitmap.set(9);
+        assert_eq!(bitmap.as_bytes(), [0b1, 0b10]);
+        assert!(bitmap.get(9));
+        assert!(!bitmap.get(10));
+        assert_eq!(bitmap.count_set(), 2);
+    }
+}
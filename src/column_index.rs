@@ -0,0 +1,181 @@
+//! Column-major field spans
+//!
+//! `ParsedCsv::indexes` lists separators row by row. A `ColumnIndex`
+//! transposes them in one pass into the start and end of every field,
+//! grouped by column, so a column can be scanned or converted without
+//! touching the others.
+
+use crate::column::{Column, MISSING};
+use crate::error::{Error, Limit, Result};
+use crate::parser::ParsedCsv;
+use std::ops::Range;
+
+/// Field spans per column, one per record
+///
+/// The header, if the input has one, is not included. A record shorter
+/// than the widest one has no span in the columns it lacks, so the index
+/// takes 8 bytes per record for every column of the widest record.
+#[derive(Clone, Debug, Default)]
+pub struct ColumnIndex {
+    columns: Vec<Vec<[u32; 2]>>,
+    rows: usize,
+}
+
+impl ColumnIndex {
+    /// Transpose the records of `buf`, which must be the buffer `pcsv` was
+    /// parsed from
+    ///
+    /// The `max_fields_per_record` and `max_index_bytes` limits `pcsv` was
+    /// parsed with also bound the width and the size of the index, so one
+    /// wide record cannot widen every other record.
+    pub fn new(pcsv: &ParsedCsv, buf: &[u8]) -> Result<Self> {
+        let max_fields = pcsv.limits.max_fields_per_record.unwrap_or(usize::MAX);
+        let max_bytes = pcsv.limits.max_index_bytes.unwrap_or(usize::MAX);
+        let mut columns: Vec<Vec<[u32; 2]>> = Vec::new();
+        let mut rows = 0;
+
+        for record in pcsv.records(buf) {
+            let offset = || record.field_range(0).map_or(0, |range| range.start);
+            if record.len() > max_fields {
+                return Err(Error::LimitExceeded {
+                    limit: Limit::FieldsPerRecord,
+                    max: max_fields,
+                    offset: offset(),
+                });
+            }
+            let bytes = columns
+                .len()
+                .max(record.len())
+                .saturating_mul(rows + 1)
+                .saturating_mul(std::mem::size_of::<[u32; 2]>());
+            if bytes > max_bytes {
+                return Err(Error::LimitExceeded {
+                    limit: Limit::IndexMemory,
+                    max: max_bytes,
+                    offset: offset(),
+                });
+            }
+            if columns.len() < record.len() {
+                // Earlier records lack the new columns
+                columns.resize_with(record.len(), || vec![[MISSING; 2]; rows]);
+            }
+            for (i, column) in columns.iter_mut().enumerate() {
+                column.push(match record.field_range(i) {
+                    Some(range) => [range.start as u32, range.end as u32],
+                    None => [MISSING; 2],
+                });
+            }
+            rows += 1;
+        }
+        Ok(Self { columns, rows })
+    }
+
+    /// Get the number of columns
+    pub fn columns(&self) -> usize {
+        self.columns.len()
+    }
+
+    /// Get the number of rows
+    pub fn rows(&self) -> usize {
+        self.rows
+    }
+
+    /// Get the byte range of the field in `row` of `col`
+    #[inline]
+    pub fn span(&self, col: usize, row: usize) -> Option<Range<usize>> {
+        let [start, end] = *self.columns.get(col)?.get(row)?;
+        (start != MISSING).then_some(start as usize..end as usize)
+    }
+
+    /// Get the raw `[start, end]` spans of `col`, with `u32::MAX` marking
+    /// a missing field
+    pub fn spans(&self, col: usize) -> Option<&[[u32; 2]]> {
+        self.columns.get(col).map(Vec::as_slice)
+    }
+
+    /// Get column `col` of `buf` for typed conversion, without copying
+    pub fn column<'a>(&'a self, buf: &'a [u8], col: usize) -> Option<Column<'a>> {
+        self.spans(col).map(|spans| Column::new(buf, spans))
+    }
+}
+
+impl ParsedCsv {
+    /// Transpose the records of `buf` into a `ColumnIndex`
+    pub fn column_index(&self, buf: &[u8]) -> Result<ColumnIndex> {
+        ColumnIndex::new(self, buf)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::limits::Limits;
+    use crate::memory::Padded;
+    use crate::parser::{parse_csv_with, ParseOptions};
+
+    #[test]
+    fn test_transpose() {
+        let data = b"a,b\n1,\"x,y\"\r\n2\n3,z,extra\n";
+        let options = ParseOptions {
+            has_header: true,
+            ..ParseOptions::default()
+        };
+        let pcsv = parse_csv_with(Padded::from_slice(data).as_padded(), &options).unwrap();
+        let index = pcsv.column_index(data).unwrap();
+
+        assert_eq!((index.columns(), index.rows()), (3, 3));
+        let field = |col, row| index.span(col, row).map(|r: Range<usize>| &data[r]);
+        assert_eq!(field(0, 0), Some(&b"1"[..]));
+        assert_eq!(field(1, 0), Some(&b"\"x,y\""[..]));
+        assert_eq!(field(1, 1), None);
+        assert_eq!(field(2, 0), None);
+        assert_eq!(field(2, 2), Some(&b"extra"[..]));
+        assert_eq!(field(0, 3), None);
+
+        let ints = index.column(data, 0).unwrap().parse_i64();
+        assert_eq!(ints.values, [1, 2, 3]);
+        let names = index.column(data, 1).unwrap();
+        assert_eq!(names.get_unquoted(0).as_deref(), Some(&b"x,y"[..]));
+        assert!(index.column(data, 3).is_none());
+    }
+
+    #[test]
+    fn test_limits() {
+        // A wide record would otherwise widen every other record
+        let mut data = b"a,b,c,d,e,f\n".to_vec();
+        data.extend(b"1\n".repeat(100));
+        let parse = |limits| {
+            let options = ParseOptions {
+                limits,
+                ..ParseOptions::default()
+            };
+            parse_csv_with(Padded::from_slice(&data).as_padded(), &options).unwrap()
+        };
+
+        let pcsv = parse(Limits {
+            max_index_bytes: Some(1000),
+            ..Limits::default()
+        });
+        let err = pcsv.column_index(&data).unwrap_err();
+        assert!(matches!(
+            err,
+            Error::LimitExceeded {
+                limit: Limit::IndexMemory,
+                max: 1000,
+                offset: 50
+            }
+        ));
+
+        // The parser keeps its own limit; the index checks it again
+        let mut pcsv = parse(Limits::default());
+        pcsv.limits.max_fields_per_record = Some(3);
+        assert!(matches!(
+            pcsv.column_index(&data),
+            Err(Error::LimitExceeded {
+                limit: Limit::FieldsPerRecord,
+                max: 3,
+                offset: 0
+            })
+        ));
+    }
+}
@@ -0,0 +1,228 @@
+//! Null and boolean tokens
+//!
+//! Tokens of up to 16 bytes are stored zero-padded in 16-byte blocks. A
+//! short field is loaded the same way, case-folded if needed, and compared
+//! against every block with one vector compare each, without allocating.
+
+use std::fmt;
+
+#[cfg(target_arch = "x86_64")]
+use std::arch::x86_64::*;
+
+/// Which field values mean missing, true and false
+///
+/// The empty field is always missing. Typed columns and schema inference
+/// classify fields with these tokens before converting them.
+#[derive(Clone, Debug)]
+pub struct ValueConventions {
+    /// Fields read as missing, like `NA`, `\N` or `NULL`
+    pub nulls: Vec<String>,
+    /// Fields read as `true`
+    pub trues: Vec<String>,
+    /// Fields read as `false`
+    pub falses: Vec<String>,
+    /// Compare ASCII letters without regard to case
+    pub ignore_case: bool,
+}
+
+impl Default for ValueConventions {
+    fn default() -> Self {
+        Self {
+            nulls: Vec::new(),
+            trues: vec!["true".to_string()],
+            falses: vec!["false".to_string()],
+            ignore_case: true,
+        }
+    }
+}
+
+/// A field that is neither a true nor a false token
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct InvalidBool;
+
+impl fmt::Display for InvalidBool {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "not a boolean")
+    }
+}
+
+impl std::error::Error for InvalidBool {}
+
+/// A compiled set of tokens
+#[derive(Clone, Debug, Default)]
+struct TokenSet {
+    blocks: Vec<[u8; 16]>,
+    lens: Vec<usize>,
+    /// Tokens longer than a block, compared byte by byte
+    long: Vec<Vec<u8>>,
+    ignore_case: bool,
+}
+
+impl TokenSet {
+    fn new(tokens: &[String], ignore_case: bool) -> Self {
+        let mut set = TokenSet {
+            ignore_case,
+            ..TokenSet::default()
+        };
+        for token in tokens {
+            let mut token = token.as_bytes().to_vec();
+            if ignore_case {
+                token.make_ascii_lowercase();
+            }
+            if token.len() > 16 {
+                set.long.push(token);
+            } else if !token.is_empty() {
+                let mut block = [0; 16];
+                block[..token.len()].copy_from_slice(&token);
+                set.blocks.push(block);
+                set.lens.push(token.len());
+            }
+        }
+        set
+    }
+
+    fn contains(&self, field: &[u8]) -> bool {
+        if field.len() > 16 {
+            return self.long.iter().any(|token| {
+                if self.ignore_case {
+                    token.eq_ignore_ascii_case(field)
+                } else {
+                    token[..] == *field
+                }
+            });
+        }
+        if self.blocks.is_empty() {
+            return false;
+        }
+        let mut block = [0; 16];
+        block[..field.len()].copy_from_slice(field);
+        self.contains_block(&block, field.len())
+    }
+
+    #[cfg(target_arch = "x86_64")]
+    fn contains_block(&self, block: &[u8; 16], len: usize) -> bool {
+        // SSE2 is part of the x86_64 baseline
+        unsafe {
+            let mut v = _mm_loadu_si128(block.as_ptr() as *const __m128i);
+            if self.ignore_case {
+                let upper = _mm_and_si128(
+                    _mm_cmpgt_epi8(v, _mm_set1_epi8(b'A' as i8 - 1)),
+                    _mm_cmplt_epi8(v, _mm_set1_epi8(b'Z' as i8 + 1)),
+                );
+                v = _mm_or_si128(v, _mm_and_si128(upper, _mm_set1_epi8(0x20)));
+            }
+            self.blocks.iter().zip(&self.lens).any(|(token, &n)| {
+                n == len
+                    && _mm_movemask_epi8(_mm_cmpeq_epi8(
+                        v,
+                        _mm_loadu_si128(token.as_ptr() as *const __m128i),
+                    )) == 0xFFFF
+            })
+        }
+    }
+
+    #[cfg(not(target_arch = "x86_64"))]
+    fn contains_block(&self, block: &[u8; 16], len: usize) -> bool {
+        let mut block = *block;
+        if self.ignore_case {
+            block.make_ascii_lowercase();
+        }
+        self.blocks
+            .iter()
+            .zip(&self.lens)
+            .any(|(token, &n)| n == len && *token == block)
+    }
+}
+
+/// `ValueConventions` compiled for matching
+#[derive(Clone, Debug)]
+pub(crate) struct Classifier {
+    nulls: TokenSet,
+    trues: TokenSet,
+    falses: TokenSet,
+}
+
+impl Classifier {
+    pub(crate) fn new(conventions: &ValueConventions) -> Self {
+        let ignore_case = conventions.ignore_case;
+        Self {
+            nulls: TokenSet::new(&conventions.nulls, ignore_case),
+            trues: TokenSet::new(&conventions.trues, ignore_case),
+            falses: TokenSet::new(&conventions.falses, ignore_case),
+        }
+    }
+
+    /// Check if `field` is empty or a null token
+    #[inline]
+    pub(crate) fn is_null(&self, field: &[u8]) -> bool {
+        field.is_empty() || self.nulls.contains(field)
+    }
+
+    /// Read a true or false token
+    pub(crate) fn parse_bool(&self, field: &[u8]) -> Result<bool, InvalidBool> {
+        if self.trues.contains(field) {
+            Ok(true)
+        } else if self.falses.contains(field) {
+            Ok(false)
+        } else {
+            Err(InvalidBool)
+        }
+    }
+}
+
+impl Default for Classifier {
+    fn default() -> Self {
+        Classifier::new(&ValueConventions::default())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn strings(tokens: &[&str]) -> Vec<String> {
+        tokens.iter().map(|t| t.to_string()).collect()
+    }
+
+    #[test]
+    fn test_classify() {
+        let conventions = ValueConventions {
+            nulls: strings(&["NA", "\\N", "NULL", "not available at this time"]),
+            trues: strings(&["Y", "true"]),
+            falses: strings(&["N", "false"]),
+            ignore_case: true,
+        };
+        let classifier = Classifier::new(&conventions);
+        for null in [
+            &b""[..],
+            b"NA",
+            b"na",
+            b"\\N",
+            b"Null",
+            b"Not Available at this time",
+        ] {
+            assert!(classifier.is_null(null), "{:?}", null);
+        }
+        for value in [&b"N"[..], b"NAN", b"N\0", b"NULL ", b"0"] {
+            assert!(!classifier.is_null(value), "{:?}", value);
+        }
+        assert_eq!(classifier.parse_bool(b"y"), Ok(true));
+        assert_eq!(classifier.parse_bool(b"TRUE"), Ok(true));
+        assert_eq!(classifier.parse_bool(b"N"), Ok(false));
+        assert_eq!(classifier.parse_bool(b"yes"), Err(InvalidBool));
+    }
+
+    #[test]
+    fn test_case_sensitive() {
+        let conventions = ValueConventions {
+            nulls: strings(&["NA"]),
+            ignore_case: false,
+            ..ValueConventions::default()
+        };
+        let classifier = Classifier::new(&conventions);
+        assert!(classifier.is_null(b"NA"));
+        assert!(!classifier.is_null(b"na"));
+        assert_eq!(classifier.parse_bool(b"True"), Err(InvalidBool));
+        assert_eq!(classifier.parse_bool(b"false"), Ok(false));
+    }
+}
@@ -0,0 +1,655 @@
+//! Date and timestamp conversion of field bytes
+//!
+//! Dates become days since 1970-01-01 and timestamps nanoseconds since
+//! 1970-01-01T00:00:00Z. ISO 8601 fields are read by `parse_date` and
+//! `parse_timestamp`; the fixed-width `YYYY-MM-DD` and `YYYY-MM-DDThh:mm`
+//! prefix is validated and converted with one 16-byte shuffle and
+//! multiply-add. Other layouts are described by a `DatePattern`.
+
+use crate::error::{Error, Result};
+use std::fmt;
+
+#[cfg(target_arch = "x86_64")]
+use std::arch::x86_64::*;
+
+const NANOS_PER_SECOND: i64 = 1_000_000_000;
+const SECONDS_PER_DAY: i64 = 86_400;
+
+const MONTHS: [&[u8]; 12] = [
+    b"january",
+    b"february",
+    b"march",
+    b"april",
+    b"may",
+    b"june",
+    b"july",
+    b"august",
+    b"september",
+    b"october",
+    b"november",
+    b"december",
+];
+
+const WEEKDAYS: [&[u8]; 7] = [
+    b"monday",
+    b"tuesday",
+    b"wednesday",
+    b"thursday",
+    b"friday",
+    b"saturday",
+    b"sunday",
+];
+
+/// Why a field is not a date or timestamp
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DateTimeError {
+    /// The field is empty
+    Empty,
+    /// The field does not match the expected layout
+    Format,
+    /// A component is out of range, like February 30, or the timestamp
+    /// does not fit `i64` nanoseconds
+    OutOfRange,
+}
+
+impl fmt::Display for DateTimeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DateTimeError::Empty => write!(f, "empty field"),
+            DateTimeError::Format => write!(f, "invalid date format"),
+            DateTimeError::OutOfRange => write!(f, "date out of range"),
+        }
+    }
+}
+
+impl std::error::Error for DateTimeError {}
+
+/// Components read from a field, before validation
+#[derive(Clone, Copy, Debug)]
+pub(crate) struct DateTime {
+    year: i64,
+    month: u32,
+    day: u32,
+    hour: u32,
+    minute: u32,
+    second: u32,
+    nanos: u32,
+    /// Seconds east of UTC
+    offset: i64,
+}
+
+impl Default for DateTime {
+    fn default() -> Self {
+        Self {
+            year: 1970,
+            month: 1,
+            day: 1,
+            hour: 0,
+            minute: 0,
+            second: 0,
+            nanos: 0,
+            offset: 0,
+        }
+    }
+}
+
+impl DateTime {
+    fn days(&self) -> std::result::Result<i32, DateTimeError> {
+        if !(1..=12).contains(&self.month)
+            || self.day == 0
+            || self.day > days_in_month(self.year, self.month)
+        {
+            return Err(DateTimeError::OutOfRange);
+        }
+        i32::try_from(days_from_civil(self.year, self.month, self.day))
+            .map_err(|_| DateTimeError::OutOfRange)
+    }
+
+    fn nanos(&self) -> std::result::Result<i64, DateTimeError> {
+        if self.hour > 23 || self.minute > 59 || self.second > 59 {
+            return Err(DateTimeError::OutOfRange);
+        }
+        let seconds = self.days()? as i64 * SECONDS_PER_DAY
+            + (self.hour * 3600 + self.minute * 60 + self.second) as i64
+            - self.offset;
+        seconds
+            .checked_mul(NANOS_PER_SECOND)
+            .and_then(|n| n.checked_add(self.nanos as i64))
+            .ok_or(DateTimeError::OutOfRange)
+    }
+}
+
+fn is_leap_year(year: i64) -> bool {
+    year % 4 == 0 && (year % 100 != 0 || year % 400 == 0)
+}
+
+fn days_in_month(year: i64, month: u32) -> u32 {
+    match month {
+        2 if is_leap_year(year) => 29,
+        2 => 28,
+        4 | 6 | 9 | 11 => 30,
+        _ => 31,
+    }
+}
+
+/// Days from 1970-01-01 to a proleptic Gregorian date
+fn days_from_civil(year: i64, month: u32, day: u32) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let year_of_era = year - era * 400;
+    // Months counted from March, so the leap day ends the year
+    let month_index = (month as i64 + 9) % 12;
+    let day_of_year = (153 * month_index + 2) / 5 + day as i64 - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    era * 146_097 + day_of_era - 719_468
+}
+
+/// Read a run of exactly `bytes.len()` ASCII digits
+fn digits(bytes: &[u8]) -> Option<u32> {
+    bytes.iter().try_fold(0u32, |acc, &b| {
+        b.is_ascii_digit().then(|| acc * 10 + (b - b'0') as u32)
+    })
+}
+
+/// Reads an ISO date and the `Thh:mm` that may follow, returning the
+/// components and the bytes read, 10 or 16; picked once per column by
+/// `iso_kernel` so the CPU is not probed for every field
+pub(crate) type IsoKernel = fn(&[u8]) -> Option<(DateTime, usize)>;
+
+/// Pick the fastest ISO prefix reader this CPU supports
+pub(crate) fn iso_kernel() -> IsoKernel {
+    #[cfg(target_arch = "x86_64")]
+    if is_x86_feature_detected!("sse4.1") {
+        return iso_sse41;
+    }
+    iso_prefix_scalar
+}
+
+/// Convert an ISO 8601 date `YYYY-MM-DD` to days since the epoch
+pub fn parse_date(field: &[u8]) -> std::result::Result<i32, DateTimeError> {
+    parse_date_with(field, iso_kernel())
+}
+
+/// Convert an ISO 8601 date or timestamp to nanoseconds since the epoch
+///
+/// Accepts `YYYY-MM-DD`, optionally followed by `T` or a space,
+/// `hh:mm[:ss[.fffffffff]]` and `Z` or a `+hh[:mm]` / `-hh[:mm]` offset.
+/// Timestamps without an offset are taken as UTC.
+pub fn parse_timestamp(field: &[u8]) -> std::result::Result<i64, DateTimeError> {
+    parse_timestamp_with(field, iso_kernel())
+}
+
+/// `parse_date` with a kernel from `iso_kernel`
+pub(crate) fn parse_date_with(
+    field: &[u8],
+    kernel: IsoKernel,
+) -> std::result::Result<i32, DateTimeError> {
+    if field.is_empty() {
+        return Err(DateTimeError::Empty);
+    }
+    match kernel(field) {
+        Some((date, 10)) if field.len() == 10 => date.days(),
+        _ => Err(DateTimeError::Format),
+    }
+}
+
+/// `parse_timestamp` with a kernel from `iso_kernel`
+pub(crate) fn parse_timestamp_with(
+    field: &[u8],
+    kernel: IsoKernel,
+) -> std::result::Result<i64, DateTimeError> {
+    if field.is_empty() {
+        return Err(DateTimeError::Empty);
+    }
+    let (mut dt, mut pos) = kernel(field).ok_or(DateTimeError::Format)?;
+    if pos == 16 {
+        if field.get(pos) == Some(&b':') {
+            dt.second = field
+                .get(pos + 1..pos + 3)
+                .and_then(digits)
+                .ok_or(DateTimeError::Format)?;
+            pos += 3;
+            if let Some(b'.' | b',') = field.get(pos) {
+                pos += 1;
+                let (nanos, len) = fraction(&field[pos..]).ok_or(DateTimeError::Format)?;
+                dt.nanos = nanos;
+                pos += len;
+            }
+        }
+        let (offset, len) = offset(&field[pos..]).ok_or(DateTimeError::Format)?;
+        dt.offset = offset;
+        pos += len;
+    }
+    if pos != field.len() {
+        return Err(DateTimeError::Format);
+    }
+    dt.nanos()
+}
+
+fn iso_prefix_scalar(field: &[u8]) -> Option<(DateTime, usize)> {
+    if field.len() < 10 || field[4] != b'-' || field[7] != b'-' {
+        return None;
+    }
+    let mut dt = DateTime {
+        year: digits(&field[0..4])? as i64,
+        month: digits(&field[5..7])?,
+        day: digits(&field[8..10])?,
+        ..DateTime::default()
+    };
+    if field.len() == 10 {
+        return Some((dt, 10));
+    }
+    if field.len() < 16 || !matches!(field[10], b'T' | b't' | b' ') || field[13] != b':' {
+        return None;
+    }
+    dt.hour = digits(&field[11..13])?;
+    dt.minute = digits(&field[14..16])?;
+    Some((dt, 16))
+}
+
+#[cfg(target_arch = "x86_64")]
+fn iso_sse41(field: &[u8]) -> Option<(DateTime, usize)> {
+    // SAFETY: only handed out by `iso_kernel` once SSE4.1 is detected
+    unsafe { iso_prefix_sse41(field) }
+}
+
+#[cfg(target_arch = "x86_64")]
+#[target_feature(enable = "sse4.1")]
+unsafe fn iso_prefix_sse41(field: &[u8]) -> Option<(DateTime, usize)> {
+    const TEMPLATE: &[u8; 16] = b"0000-00-00T00:00";
+    // Bits of the '-', 'T' and ':' positions in TEMPLATE
+    const SEPARATORS: i32 = 1 << 4 | 1 << 7 | 1 << 10 | 1 << 13;
+
+    // A date alone is completed with a midnight time so the layout is fixed
+    let mut block = *TEMPLATE;
+    let len = match field.len() {
+        10 => 10,
+        n if n >= 16 && matches!(field[10], b'T' | b't' | b' ') => 16,
+        _ => return None,
+    };
+    block[..len].copy_from_slice(&field[..len]);
+    block[10] = b'T';
+
+    let v = _mm_loadu_si128(block.as_ptr() as *const __m128i);
+    let template = _mm_loadu_si128(TEMPLATE.as_ptr() as *const __m128i);
+    if _mm_movemask_epi8(_mm_cmpeq_epi8(v, template)) & SEPARATORS != SEPARATORS {
+        return None;
+    }
+
+    // Gather the 12 digits into adjacent pairs; the last 4 lanes become 0
+    let digits = _mm_shuffle_epi8(
+        _mm_sub_epi8(v, _mm_set1_epi8(b'0' as i8)),
+        _mm_setr_epi8(0, 1, 2, 3, 5, 6, 8, 9, 11, 12, 14, 15, -1, -1, -1, -1),
+    );
+    let nine = _mm_set1_epi8(9);
+    if _mm_movemask_epi8(_mm_cmpeq_epi8(_mm_max_epu8(digits, nine), nine)) != 0xFFFF {
+        return None;
+    }
+    let pairs = _mm_maddubs_epi16(
+        digits,
+        _mm_setr_epi8(10, 1, 10, 1, 10, 1, 10, 1, 10, 1, 10, 1, 10, 1, 10, 1),
+    );
+    let mut lanes = [0u16; 8];
+    _mm_storeu_si128(lanes.as_mut_ptr() as *mut __m128i, pairs);
+
+    let dt = DateTime {
+        year: (lanes[0] * 100 + lanes[1]) as i64,
+        month: lanes[2] as u32,
+        day: lanes[3] as u32,
+        hour: lanes[4] as u32,
+        minute: lanes[5] as u32,
+        ..DateTime::default()
+    };
+    Some((dt, len))
+}
+
+/// Read 1 to 9 fraction digits as nanoseconds; further digits are dropped
+fn fraction(bytes: &[u8]) -> Option<(u32, usize)> {
+    let len = bytes.iter().take_while(|b| b.is_ascii_digit()).count();
+    if len == 0 {
+        return None;
+    }
+    let kept = len.min(9);
+    let nanos = digits(&bytes[..kept])? * 10u32.pow(9 - kept as u32);
+    Some((nanos, len))
+}
+
+/// Read an optional `Z`, `+hh`, `+hhmm` or `+hh:mm` offset in seconds
+fn offset(bytes: &[u8]) -> Option<(i64, usize)> {
+    let sign = match bytes.first() {
+        None => return Some((0, 0)),
+        Some(b'Z' | b'z') => return Some((0, 1)),
+        Some(b'+') => 1,
+        Some(b'-') => -1,
+        Some(_) => return None,
+    };
+    let hours = digits(bytes.get(1..3)?)? as i64;
+    let (minutes, len) = match bytes.get(3) {
+        Some(b':') => (digits(bytes.get(4..6)?)?, 6),
+        Some(_) => (digits(bytes.get(3..5)?)?, 5),
+        None => (0, 3),
+    };
+    if hours > 23 || minutes > 59 {
+        return None;
+    }
+    Some((sign * (hours * 3600 + minutes as i64 * 60), len))
+}
+
+/// One step of a `DatePattern`
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Item {
+    Literal(u8),
+    /// Any run of whitespace, including none
+    Space,
+    Year,
+    YearOfCentury,
+    Month,
+    MonthName,
+    Day,
+    Hour,
+    Hour12,
+    Minute,
+    Second,
+    Fraction,
+    AmPm,
+    Weekday,
+    Offset,
+}
+
+/// A strftime-like layout for dates and timestamps
+///
+/// Supported directives:
+///
+/// | Directive | Meaning |
+/// |-----------|---------|
+/// | `%Y` | year, 4 digits |
+/// | `%y` | year of century, 69-99 are 1969-1999 and 00-68 are 2000-2068 |
+/// | `%m` | month, 1 or 2 digits |
+/// | `%b`, `%B` | month name, abbreviated or full, any case |
+/// | `%d`, `%e` | day of month, 1 or 2 digits |
+/// | `%H` | hour 0-23, `%I` hour 1-12 with `%p` AM or PM |
+/// | `%M`, `%S` | minute, second |
+/// | `%f` | fraction of a second, 1 to 9 digits |
+/// | `%a`, `%A` | weekday name, checked for spelling but not against the date |
+/// | `%z` | `Z` or a `+hh[:mm]` offset |
+/// | `%%` | a literal `%` |
+///
+/// A space in the pattern matches any run of whitespace. Components the
+/// pattern leaves out default to 1970-01-01T00:00:00Z.
+#[derive(Clone, Debug)]
+pub struct DatePattern {
+    items: Vec<Item>,
+}
+
+impl DatePattern {
+    /// Compile `pattern`
+    pub fn new(pattern: &str) -> Result<Self> {
+        let invalid = || Error::InvalidPattern {
+            pattern: pattern.to_string(),
+        };
+        let mut items = Vec::new();
+        let mut bytes = pattern.bytes();
+        while let Some(b) = bytes.next() {
+            let item = match b {
+                b'%' => match bytes.next().ok_or_else(invalid)? {
+                    b'Y' => Item::Year,
+                    b'y' => Item::YearOfCentury,
+                    b'm' => Item::Month,
+                    b'b' | b'B' | b'h' => Item::MonthName,
+                    b'd' | b'e' => Item::Day,
+                    b'H' => Item::Hour,
+                    b'I' => Item::Hour12,
+                    b'M' => Item::Minute,
+                    b'S' => Item::Second,
+                    b'f' => Item::Fraction,
+                    b'p' => Item::AmPm,
+                    b'a' | b'A' => Item::Weekday,
+                    b'z' => Item::Offset,
+                    b'%' => Item::Literal(b'%'),
+                    _ => return Err(invalid()),
+                },
+                b if b.is_ascii_whitespace() => Item::Space,
+                b => Item::Literal(b),
+            };
+            items.push(item);
+        }
+        Ok(Self { items })
+    }
+
+    /// Convert `field` to days since the epoch, ignoring any time of day
+    pub fn parse_date(&self, field: &[u8]) -> std::result::Result<i32, DateTimeError> {
+        self.read(field)?.days()
+    }
+
+    /// Convert `field` to nanoseconds since the epoch
+    pub fn parse_timestamp(&self, field: &[u8]) -> std::result::Result<i64, DateTimeError> {
+        self.read(field)?.nanos()
+    }
+
+    fn read(&self, field: &[u8]) -> std::result::Result<DateTime, DateTimeError> {
+        if field.is_empty() {
+            return Err(DateTimeError::Empty);
+        }
+        let mut dt = DateTime::default();
+        let mut pm = None;
+        let mut rest = field;
+
+        for &item in &self.items {
+            let len = match item {
+                Item::Literal(b) => (rest.first() == Some(&b)).then_some(1),
+                Item::Space => Some(rest.iter().take_while(|b| b.is_ascii_whitespace()).count()),
+                Item::Year => number(rest, 4).map(|(v, len)| {
+                    dt.year = v as i64;
+                    len
+                }),
+                Item::YearOfCentury => number(rest, 2).map(|(v, len)| {
+                    dt.year = if v < 69 { 2000 } else { 1900 } + v as i64;
+                    len
+                }),
+                Item::Month => number(rest, 2).map(|(v, len)| {
+                    dt.month = v;
+                    len
+                }),
+                Item::MonthName => name(rest, &MONTHS).map(|(i, len)| {
+                    dt.month = i as u32 + 1;
+                    len
+                }),
+                Item::Day => {
+                    let pad = rest.iter().take_while(|&&b| b == b' ').count().min(1);
+                    number(&rest[pad..], 2).map(|(v, len)| {
+                        dt.day = v;
+                        pad + len
+                    })
+                }
+                Item::Hour | Item::Hour12 => number(rest, 2).map(|(v, len)| {
+                    dt.hour = v;
+                    len
+                }),
+                Item::Minute => number(rest, 2).map(|(v, len)| {
+                    dt.minute = v;
+                    len
+                }),
+                Item::Second => number(rest, 2).map(|(v, len)| {
+                    dt.second = v;
+                    len
+                }),
+                Item::Fraction => fraction(rest).map(|(v, len)| {
+                    dt.nanos = v;
+                    len
+                }),
+                Item::AmPm => {
+                    let marker = rest.get(..2).unwrap_or_default();
+                    if marker.eq_ignore_ascii_case(b"am") {
+                        pm = Some(false);
+                        Some(2)
+                    } else if marker.eq_ignore_ascii_case(b"pm") {
+                        pm = Some(true);
+                        Some(2)
+                    } else {
+                        None
+                    }
+                }
+                Item::Weekday => name(rest, &WEEKDAYS).map(|(_, len)| len),
+                Item::Offset => match offset(rest) {
+                    Some((_, 0)) | None => None,
+                    Some((v, len)) => {
+                        dt.offset = v;
+                        Some(len)
+                    }
+                },
+            };
+            rest = &rest[len.ok_or(DateTimeError::Format)?..];
+        }
+        if !rest.is_empty() {
+            return Err(DateTimeError::Format);
+        }
+
+        if let Some(pm) = pm {
+            if !(1..=12).contains(&dt.hour) {
+                return Err(DateTimeError::OutOfRange);
+            }
+            dt.hour = dt.hour % 12 + if pm { 12 } else { 0 };
+        }
+        Ok(dt)
+    }
+}
+
+/// Read 1 to `max` digits
+fn number(bytes: &[u8], max: usize) -> Option<(u32, usize)> {
+    let len = bytes
+        .iter()
+        .take(max)
+        .take_while(|b| b.is_ascii_digit())
+        .count();
+    (len > 0).then(|| (digits(&bytes[..len]).unwrap_or_default(), len))
+}
+
+/// Match a full name or its three-letter abbreviation, in any case
+fn name(bytes: &[u8], names: &[&[u8]]) -> Option<(usize, usize)> {
+    names.iter().enumerate().find_map(|(i, name)| {
+        let starts = |len: usize| {
+            bytes
+                .get(..len)
+                .is_some_and(|b| b.eq_ignore_ascii_case(&name[..len]))
+        };
+        if starts(name.len()) {
+            Some((i, name.len()))
+        } else {
+            starts(3).then_some((i, 3))
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DAY_NANOS: i64 = SECONDS_PER_DAY * NANOS_PER_SECOND;
+
+    #[test]
+    fn test_iso() {
+        assert_eq!(parse_date(b"1970-01-01"), Ok(0));
+        assert_eq!(parse_date(b"2012-08-16"), Ok(15568));
+        assert_eq!(parse_date(b"1969-12-31"), Ok(-1));
+        assert_eq!(parse_date(b"2000-02-29"), Ok(11016));
+        assert_eq!(parse_date(b"2001-02-29"), Err(DateTimeError::OutOfRange));
+        assert_eq!(parse_date(b"2012-8-16"), Err(DateTimeError::Format));
+        assert_eq!(parse_date(b"2012-08-16 00:00"), Err(DateTimeError::Format));
+        assert_eq!(parse_date(b""), Err(DateTimeError::Empty));
+
+        let base = 15671 * DAY_NANOS + (16 * 60 + 56) * NANOS_PER_SECOND;
+        assert_eq!(parse_timestamp(b"2012-11-27 00:16:56"), Ok(base));
+        assert_eq!(parse_timestamp(b"2012-11-27T00:16:56Z"), Ok(base));
+        assert_eq!(
+            parse_timestamp(b"2012-11-27T00:16:56.5"),
+            Ok(base + 500_000_000)
+        );
+        assert_eq!(
+            parse_timestamp(b"2012-11-27T00:16:56,123456789123"),
+            Ok(base + 123_456_789)
+        );
+        assert_eq!(parse_timestamp(b"2012-11-27T02:16:56+02:00"), Ok(base));
+        assert_eq!(parse_timestamp(b"2012-11-26T22:46:56-0130"), Ok(base));
+        assert_eq!(
+            parse_timestamp(b"2012-11-27T00:16"),
+            Ok(base - 56 * NANOS_PER_SECOND)
+        );
+        assert_eq!(parse_timestamp(b"2012-11-27"), Ok(15671 * DAY_NANOS));
+        assert_eq!(
+            parse_timestamp(b"2012-11-27T24:00"),
+            Err(DateTimeError::OutOfRange)
+        );
+        assert_eq!(
+            parse_timestamp(b"2300-01-01"),
+            Err(DateTimeError::OutOfRange)
+        );
+        for bad in [
+            &b"2012-11-27T00:16:5"[..],
+            b"2012-11-27T00",
+            b"2012-11-27x00:16",
+            b"2012-11-27T00:16:56+2",
+        ] {
+            assert_eq!(
+                parse_timestamp(bad),
+                Err(DateTimeError::Format),
+                "{:?}",
+                bad
+            );
+        }
+    }
+
+    #[test]
+    fn test_simd_matches_scalar() {
+        for field in [
+            &b"2012-08-16"[..],
+            b"2012-08-16T23:59",
+            b"2012-08-16 23:59:01",
+            b"2012/08/16",
+            b"2012-08-1a",
+            b"2012-08-16X23:59",
+            b"2012-08-16T23-59",
+            b"9999-99-99T99:99",
+            b"2012-08-16T2",
+        ] {
+            let scalar = iso_prefix_scalar(field).map(|(dt, len)| (dt.nanos(), len));
+            let dispatched = iso_kernel()(field).map(|(dt, len)| (dt.nanos(), len));
+            assert_eq!(scalar, dispatched, "{:?}", field);
+        }
+    }
+
+    #[test]
+    fn test_patterns() {
+        let pattern = DatePattern::new("%d-%b-%Y").unwrap();
+        assert_eq!(pattern.parse_date(b"16-Aug-2012"), Ok(15568));
+        assert_eq!(pattern.parse_date(b"16-AUGUST-2012"), Ok(15568));
+        assert_eq!(
+            DatePattern::new("%d-%b-%y")
+                .unwrap()
+                .parse_date(b"16-Aug-12"),
+            Ok(15568)
+        );
+        assert_eq!(
+            pattern.parse_date(b"16/Aug/2012"),
+            Err(DateTimeError::Format)
+        );
+
+        let pattern = DatePattern::new("%a %m-%d-%y").unwrap();
+        assert_eq!(pattern.parse_date(b"Fri 08-16-12"), Ok(15568));
+
+        let pattern = DatePattern::new("%m/%d/%Y %I:%M:%S.%f %p %z").unwrap();
+        assert_eq!(
+            pattern.parse_timestamp(b"11/27/2012 12:16:56.25 AM +00:00"),
+            parse_timestamp(b"2012-11-27T00:16:56.25Z")
+        );
+        assert_eq!(
+            pattern.parse_timestamp(b"11/27/2012  1:16:56.25 pm -01"),
+            parse_timestamp(b"2012-11-27T14:16:56.25Z")
+        );
+
+        assert!(matches!(
+            DatePattern::new("%Y-%q"),
+            Err(Error::InvalidPattern { .. })
+        ));
+        assert!(DatePattern::new("%").is_err());
+    }
+}
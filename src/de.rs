@@ -0,0 +1,526 @@
+//! Serde deserialization of records
+//!
+//! A record deserializes as a sequence of fields, or as a map from header
+//! names to fields when the input has a header. Fields are unquoted first;
+//! a field without doubled quotes is borrowed from the buffer, so `&str`
+//! and `&[u8]` members cost no copy.
+
+use crate::error::{Error, Result};
+use crate::float::parse_f64;
+use crate::numeric::{parse_i64, parse_u64};
+use crate::record::{Record, Records};
+use serde::de::value::{BorrowedStrDeserializer, StringDeserializer};
+use serde::de::{self, Deserialize, DeserializeSeed, Unexpected, Visitor};
+use std::borrow::Cow;
+use std::fmt;
+use std::marker::PhantomData;
+
+impl de::Error for Error {
+    fn custom<T: fmt::Display>(msg: T) -> Self {
+        Error::Deserialize {
+            offset: 0,
+            field: None,
+            message: msg.to_string(),
+        }
+    }
+}
+
+/// Name the field a deserialization error happened in, unless known
+fn locate(err: Error, field: impl FnOnce() -> String) -> Error {
+    match err {
+        Error::Deserialize {
+            offset,
+            field: None,
+            message,
+        } => Error::Deserialize {
+            offset,
+            field: Some(field()),
+            message,
+        },
+        other => other,
+    }
+}
+
+impl<'a> Record<'a> {
+    /// Deserialize the record into `T`
+    ///
+    /// Structs are filled by position, or by name when `header` is given.
+    pub fn deserialize<T: Deserialize<'a>>(&self, header: Option<&Record<'a>>) -> Result<T> {
+        let mut deserializer = RecordDeserializer {
+            record: *self,
+            header: header.copied(),
+            next: 0,
+        };
+        T::deserialize(&mut deserializer).map_err(|e| match e {
+            Error::Deserialize { field, message, .. } => Error::Deserialize {
+                offset: self.range().start,
+                field,
+                message,
+            },
+            other => other,
+        })
+    }
+}
+
+impl<'a> Records<'a> {
+    /// Deserialize every remaining record into `T`, by header name if the
+    /// input has a header
+    pub fn deserialize<T: Deserialize<'a>>(self) -> DeserializeRecords<'a, T> {
+        DeserializeRecords {
+            records: self,
+            marker: PhantomData,
+        }
+    }
+}
+
+/// Iterator returned by `Records::deserialize`
+pub struct DeserializeRecords<'a, T> {
+    records: Records<'a>,
+    marker: PhantomData<T>,
+}
+
+impl<'a, T: Deserialize<'a>> Iterator for DeserializeRecords<'a, T> {
+    type Item = Result<T>;
+
+    fn next(&mut self) -> Option<Result<T>> {
+        let record = self.records.next()?;
+        Some(record.deserialize(self.records.header.as_ref()))
+    }
+}
+
+struct RecordDeserializer<'a> {
+    record: Record<'a>,
+    header: Option<Record<'a>>,
+    /// Next field to hand out
+    next: usize,
+}
+
+impl RecordDeserializer<'_> {
+    fn field_name(&self, i: usize) -> String {
+        match self.header.and_then(|h| h.get_unquoted(i)) {
+            Some(name) => format!("'{}'", String::from_utf8_lossy(&name)),
+            None => i.to_string(),
+        }
+    }
+}
+
+impl<'de> de::Deserializer<'de> for &mut RecordDeserializer<'de> {
+    type Error = Error;
+
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        if self.header.is_some() {
+            visitor.visit_map(self)
+        } else {
+            visitor.visit_seq(self)
+        }
+    }
+
+    fn deserialize_map<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        if self.header.is_none() {
+            return Err(de::Error::custom("a map needs the input to have a header"));
+        }
+        visitor.visit_map(self)
+    }
+
+    fn deserialize_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        _fields: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value> {
+        self.deserialize_any(visitor)
+    }
+
+    fn deserialize_seq<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        visitor.visit_seq(self)
+    }
+
+    fn deserialize_tuple<V: Visitor<'de>>(self, _len: usize, visitor: V) -> Result<V::Value> {
+        visitor.visit_seq(self)
+    }
+
+    fn deserialize_tuple_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        _len: usize,
+        visitor: V,
+    ) -> Result<V::Value> {
+        visitor.visit_seq(self)
+    }
+
+    fn deserialize_newtype_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value> {
+        visitor.visit_newtype_struct(self)
+    }
+
+    serde::forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string
+        bytes byte_buf option unit unit_struct enum identifier ignored_any
+    }
+}
+
+impl<'de> de::SeqAccess<'de> for RecordDeserializer<'de> {
+    type Error = Error;
+
+    fn next_element_seed<T: DeserializeSeed<'de>>(&mut self, seed: T) -> Result<Option<T::Value>> {
+        let i = self.next;
+        let Some(field) = self.record.get_unquoted(i) else {
+            return Ok(None);
+        };
+        self.next += 1;
+        seed.deserialize(FieldDeserializer(field))
+            .map(Some)
+            .map_err(|e| locate(e, || self.field_name(i)))
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        Some(self.record.len() - self.next)
+    }
+}
+
+impl<'de> de::MapAccess<'de> for RecordDeserializer<'de> {
+    type Error = Error;
+
+    fn next_key_seed<K: DeserializeSeed<'de>>(&mut self, seed: K) -> Result<Option<K::Value>> {
+        // Columns past the end of a short record are missing, not empty
+        if self.next >= self.record.len() {
+            return Ok(None);
+        }
+        match self.header.and_then(|h| h.get_unquoted(self.next)) {
+            Some(name) => seed.deserialize(FieldDeserializer(name)).map(Some),
+            None => Ok(None),
+        }
+    }
+
+    fn next_value_seed<V: DeserializeSeed<'de>>(&mut self, seed: V) -> Result<V::Value> {
+        let i = self.next;
+        self.next += 1;
+        let field = self.record.get_unquoted(i).unwrap_or_default();
+        seed.deserialize(FieldDeserializer(field))
+            .map_err(|e| locate(e, || self.field_name(i)))
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        let columns = self.header.map_or(0, |h| h.len());
+        Some(columns.min(self.record.len()).saturating_sub(self.next))
+    }
+}
+
+/// One unquoted field
+struct FieldDeserializer<'a>(Cow<'a, [u8]>);
+
+impl<'a> FieldDeserializer<'a> {
+    fn invalid(&self, expected: &'static str) -> Error {
+        let unexpected = match std::str::from_utf8(&self.0) {
+            Ok(s) => Unexpected::Str(s),
+            Err(_) => Unexpected::Bytes(&self.0),
+        };
+        de::Error::invalid_value(unexpected, &expected)
+    }
+
+    fn text(&self) -> Result<&str> {
+        std::str::from_utf8(&self.0).map_err(|_| self.invalid("a UTF-8 string"))
+    }
+
+    fn int<T: TryFrom<i64>>(&self, expected: &'static str) -> Result<T> {
+        parse_i64(&self.0)
+            .ok()
+            .and_then(|v| T::try_from(v).ok())
+            .ok_or_else(|| self.invalid(expected))
+    }
+
+    fn uint<T: TryFrom<u64>>(&self, expected: &'static str) -> Result<T> {
+        parse_u64(&self.0)
+            .ok()
+            .and_then(|v| T::try_from(v).ok())
+            .ok_or_else(|| self.invalid(expected))
+    }
+
+    fn parsed<T: std::str::FromStr>(&self, expected: &'static str) -> Result<T> {
+        self.text()?.parse().map_err(|_| self.invalid(expected))
+    }
+}
+
+macro_rules! deserialize_number {
+    ($($method:ident => $visit:ident, $parse:ident, $expected:literal;)*) => {
+        $(
+            fn $method<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+                visitor.$visit(self.$parse($expected)?)
+            }
+        )*
+    };
+}
+
+impl<'de> de::Deserializer<'de> for FieldDeserializer<'de> {
+    type Error = Error;
+
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        match self.0 {
+            Cow::Borrowed(bytes) => match std::str::from_utf8(bytes) {
+                Ok(s) => visitor.visit_borrowed_str(s),
+                Err(_) => visitor.visit_borrowed_bytes(bytes),
+            },
+            Cow::Owned(bytes) => match String::from_utf8(bytes) {
+                Ok(s) => visitor.visit_string(s),
+                Err(e) => visitor.visit_byte_buf(e.into_bytes()),
+            },
+        }
+    }
+
+    fn deserialize_bool<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        if self.0.eq_ignore_ascii_case(b"true") {
+            visitor.visit_bool(true)
+        } else if self.0.eq_ignore_ascii_case(b"false") {
+            visitor.visit_bool(false)
+        } else {
+            Err(self.invalid("true or false"))
+        }
+    }
+
+    deserialize_number! {
+        deserialize_i8 => visit_i8, int, "an i8";
+        deserialize_i16 => visit_i16, int, "an i16";
+        deserialize_i32 => visit_i32, int, "an i32";
+        deserialize_i64 => visit_i64, int, "an i64";
+        deserialize_u8 => visit_u8, uint, "a u8";
+        deserialize_u16 => visit_u16, uint, "a u16";
+        deserialize_u32 => visit_u32, uint, "a u32";
+        deserialize_u64 => visit_u64, uint, "a u64";
+        deserialize_i128 => visit_i128, parsed, "an i128";
+        deserialize_u128 => visit_u128, parsed, "a u128";
+        // Going through f64 could round twice
+        deserialize_f32 => visit_f32, parsed, "an f32";
+    }
+
+    fn deserialize_f64<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        let value = parse_f64(&self.0).map_err(|_| self.invalid("an f64"))?;
+        visitor.visit_f64(value)
+    }
+
+    fn deserialize_char<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        let mut chars = self.text()?.chars();
+        match (chars.next(), chars.next()) {
+            (Some(c), None) => visitor.visit_char(c),
+            _ => Err(self.invalid("a single character")),
+        }
+    }
+
+    fn deserialize_str<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        self.deserialize_string(visitor)
+    }
+
+    fn deserialize_string<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        match self.0 {
+            Cow::Borrowed(bytes) => match std::str::from_utf8(bytes) {
+                Ok(s) => visitor.visit_borrowed_str(s),
+                Err(_) => Err(FieldDeserializer(Cow::Borrowed(bytes)).invalid("a UTF-8 string")),
+            },
+            Cow::Owned(bytes) => match String::from_utf8(bytes) {
+                Ok(s) => visitor.visit_string(s),
+                Err(e) => {
+                    Err(FieldDeserializer(Cow::Owned(e.into_bytes())).invalid("a UTF-8 string"))
+                }
+            },
+        }
+    }
+
+    fn deserialize_bytes<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        self.deserialize_byte_buf(visitor)
+    }
+
+    fn deserialize_byte_buf<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        match self.0 {
+            Cow::Borrowed(bytes) => visitor.visit_borrowed_bytes(bytes),
+            Cow::Owned(bytes) => visitor.visit_byte_buf(bytes),
+        }
+    }
+
+    fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        if self.0.is_empty() {
+            visitor.visit_none()
+        } else {
+            visitor.visit_some(self)
+        }
+    }
+
+    fn deserialize_unit<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        visitor.visit_unit()
+    }
+
+    fn deserialize_unit_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value> {
+        visitor.visit_unit()
+    }
+
+    fn deserialize_newtype_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value> {
+        visitor.visit_newtype_struct(self)
+    }
+
+    /// Unit variants, named by the field
+    fn deserialize_enum<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        _variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value> {
+        match self.0 {
+            Cow::Borrowed(bytes) => {
+                let name = std::str::from_utf8(bytes).map_err(|_| {
+                    FieldDeserializer(Cow::Borrowed(bytes)).invalid("a variant name")
+                })?;
+                visitor.visit_enum(BorrowedStrDeserializer::new(name))
+            }
+            Cow::Owned(_) => {
+                let name = self.text()?.to_string();
+                visitor.visit_enum(StringDeserializer::new(name))
+            }
+        }
+    }
+
+    fn deserialize_identifier<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        self.deserialize_string(visitor)
+    }
+
+    fn deserialize_ignored_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        visitor.visit_unit()
+    }
+
+    serde::forward_to_deserialize_any! {
+        seq tuple tuple_struct map struct
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::error::Error;
+    use crate::memory::Padded;
+    use crate::parser::{parse_csv_with, ParseOptions};
+    use serde::Deserialize;
+
+    #[derive(Debug, Deserialize, PartialEq)]
+    struct Play<'a> {
+        gameid: &'a str,
+        qtr: i16,
+        min: Option<i16>,
+        sec: Option<i16>,
+        off: &'a str,
+        def: &'a str,
+        down: Option<i16>,
+        togo: Option<i16>,
+        ydline: Option<i16>,
+        description: String,
+        offscore: i16,
+        defscore: i16,
+        season: u16,
+    }
+
+    #[test]
+    fn test_deserialize_by_name() {
+        let data = std::fs::read("examples/nfl.csv").unwrap();
+        let options = ParseOptions {
+            has_header: true,
+            ..ParseOptions::default()
+        };
+        let padded = Padded::from_slice(&data);
+        let pcsv = parse_csv_with(padded.as_padded(), &options).unwrap();
+
+        let plays: Vec<Play> = pcsv
+            .records(&data)
+            .deserialize()
+            .collect::<Result<_, _>>()
+            .unwrap();
+        assert_eq!(plays.len(), pcsv.records(&data).count());
+        assert_eq!(
+            plays[1],
+            Play {
+                gameid: "20120905_DAL@NYG",
+                qtr: 1,
+                min: Some(59),
+                sec: Some(56),
+                off: "NYG",
+                def: "DAL",
+                down: Some(1),
+                togo: Some(10),
+                ydline: Some(84),
+                description: "(14:56) E.Manning pass incomplete deep left to H.Nicks.".into(),
+                offscore: 0,
+                defscore: 0,
+                season: 2012,
+            }
+        );
+        assert_eq!(plays[0].min, None);
+    }
+
+    #[derive(Debug, Deserialize, PartialEq)]
+    enum Kind {
+        Run,
+        Pass,
+    }
+
+    #[test]
+    fn test_deserialize_by_position() {
+        let data = b"1,\"a \"\"b\"\"\",Pass,2.5,x\n2,c,Run,,y\n";
+        let pcsv = parse_csv_with(
+            Padded::from_slice(data).as_padded(),
+            &ParseOptions::default(),
+        )
+        .unwrap();
+
+        type Row<'a> = (u32, String, Kind, Option<f64>, &'a [u8]);
+        let rows: Vec<Row> = pcsv
+            .records(data)
+            .deserialize()
+            .collect::<Result<_, _>>()
+            .unwrap();
+        assert_eq!(
+            rows[0],
+            (1, "a \"b\"".to_string(), Kind::Pass, Some(2.5), &b"x"[..])
+        );
+        assert_eq!(rows[1], (2, "c".to_string(), Kind::Run, None, &b"y"[..]));
+
+        // Escaped quotes cannot be borrowed
+        let err = pcsv
+            .records(data)
+            .deserialize::<(u32, &str)>()
+            .next()
+            .unwrap()
+            .unwrap_err();
+        assert!(
+            matches!(err, Error::Deserialize { offset: 0, field: Some(ref f), .. } if f == "1")
+        );
+    }
+
+    #[test]
+    fn test_deserialize_errors() {
+        #[derive(Debug, Deserialize)]
+        #[allow(dead_code)]
+        struct Row {
+            id: u8,
+            name: String,
+        }
+
+        let data = b"id,name\n1,a\n300,b\n";
+        let options = ParseOptions {
+            has_header: true,
+            ..ParseOptions::default()
+        };
+        let pcsv = parse_csv_with(Padded::from_slice(data).as_padded(), &options).unwrap();
+        let rows: Vec<_> = pcsv.records(data).deserialize::<Row>().collect();
+        assert!(rows[0].is_ok());
+        let err = rows[1].as_ref().unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "field 'id' of the record at byte 12: invalid value: string \"300\", expected a u8"
+        );
+    }
+}
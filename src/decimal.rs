@@ -0,0 +1,245 @@
+//! Fixed-point decimal conversion of field bytes
+//!
+//! A decimal is read straight into an `i128` mantissa at a fixed scale, so
+//! `12.30` at scale 2 becomes `1230`, without passing through a float.
+
+use std::fmt;
+
+/// Largest supported precision: `10^38 - 1` still fits `i128`
+pub const MAX_PRECISION: u32 = 38;
+
+/// Layout and size of the decimals in a column
+#[derive(Clone, Debug)]
+pub struct DecimalFormat {
+    /// Total number of digits, at most `MAX_PRECISION`
+    pub precision: u32,
+    /// Number of digits after the decimal separator
+    pub scale: u32,
+    /// Decimal separator, `.` or `,` for European data
+    pub decimal: u8,
+    /// Thousands separator in the integer part, in groups of three
+    pub thousands: Option<u8>,
+}
+
+impl Default for DecimalFormat {
+    fn default() -> Self {
+        Self {
+            precision: MAX_PRECISION,
+            scale: 2,
+            decimal: b'.',
+            thousands: None,
+        }
+    }
+}
+
+/// Why a field is not a decimal of the expected format
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DecimalError {
+    /// The field is empty
+    Empty,
+    /// The field holds a byte that does not belong in the number, or a
+    /// misplaced thousands separator
+    InvalidDigit,
+    /// The field has non-zero digits beyond the scale
+    Rounding,
+    /// The value has more digits than the precision allows
+    Overflow,
+}
+
+impl fmt::Display for DecimalError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DecimalError::Empty => write!(f, "empty field"),
+            DecimalError::InvalidDigit => write!(f, "invalid digit"),
+            DecimalError::Rounding => write!(f, "more decimal places than the scale"),
+            DecimalError::Overflow => write!(f, "decimal too large for the precision"),
+        }
+    }
+}
+
+impl std::error::Error for DecimalError {}
+
+impl DecimalFormat {
+    /// Convert `field` to a mantissa at this format's scale
+    ///
+    /// Accepts a leading `+` or `-`, digits with optional thousands
+    /// separators, and an optional decimal separator followed by digits.
+    /// Trailing zeros beyond the scale are dropped; other digits there are
+    /// a `Rounding` error rather than silently rounded.
+    pub fn parse(&self, field: &[u8]) -> Result<i128, DecimalError> {
+        let (negative, rest) = match field {
+            [] => return Err(DecimalError::Empty),
+            [b'-', rest @ ..] => (true, rest),
+            [b'+', rest @ ..] => (false, rest),
+            rest => (false, rest),
+        };
+        let precision = self.precision.min(MAX_PRECISION);
+        let limit = 10i128.pow(precision);
+
+        let mut mantissa: i128 = 0;
+        let mut any_digit = false;
+        let mut overflow = false;
+        let mut push = |mantissa: &mut i128, digit: u8| {
+            match mantissa.checked_mul(10).map(|m| m + digit as i128) {
+                Some(m) if m < limit => *mantissa = m,
+                // Keep validating the rest of the field
+                _ => {
+                    overflow = true;
+                    *mantissa = 0;
+                }
+            }
+        };
+
+        // Integer part; `group` counts digits since the last thousands separator
+        let mut i = 0;
+        let mut group = 0;
+        let mut grouped = false;
+        while let Some(&b) = rest.get(i) {
+            if b.is_ascii_digit() {
+                push(&mut mantissa, b - b'0');
+                any_digit = true;
+                group += 1;
+            } else if Some(b) == self.thousands {
+                let valid = if grouped {
+                    group == 3
+                } else {
+                    (1..=3).contains(&group)
+                };
+                if !valid {
+                    return Err(DecimalError::InvalidDigit);
+                }
+                grouped = true;
+                group = 0;
+            } else {
+                break;
+            }
+            i += 1;
+        }
+        if grouped && group != 3 {
+            return Err(DecimalError::InvalidDigit);
+        }
+
+        // Fraction part, padded with zeros up to the scale
+        let mut places = 0;
+        let mut rounding = false;
+        if rest.get(i) == Some(&self.decimal) {
+            i += 1;
+            while let Some(&b) = rest.get(i).filter(|b| b.is_ascii_digit()) {
+                if places < self.scale {
+                    push(&mut mantissa, b - b'0');
+                    places += 1;
+                } else if b != b'0' {
+                    rounding = true;
+                }
+                any_digit = true;
+                i += 1;
+            }
+        }
+        if !any_digit || i != rest.len() {
+            return Err(DecimalError::InvalidDigit);
+        }
+        for _ in places..self.scale {
+            push(&mut mantissa, 0);
+        }
+
+        if overflow {
+            Err(DecimalError::Overflow)
+        } else if rounding {
+            Err(DecimalError::Rounding)
+        } else {
+            Ok(if negative { -mantissa } else { mantissa })
+        }
+    }
+}
+
+/// Render `mantissa` at `scale` as a decimal string with a `.` separator
+pub fn format_decimal(mantissa: i128, scale: u32) -> String {
+    let digits = mantissa.unsigned_abs().to_string();
+    let scale = scale as usize;
+    let sign = if mantissa < 0 { "-" } else { "" };
+    if scale == 0 {
+        return format!("{}{}", sign, digits);
+    }
+    let digits = format!("{:0>width$}", digits, width = scale + 1);
+    let (int, frac) = digits.split_at(digits.len() - scale);
+    format!("{}{}.{}", sign, int, frac)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_decimals() {
+        let format = DecimalFormat::default();
+        assert_eq!(format.parse(b"12.3"), Ok(1230));
+        assert_eq!(format.parse(b"-0.05"), Ok(-5));
+        assert_eq!(format.parse(b"+7"), Ok(700));
+        assert_eq!(format.parse(b".5"), Ok(50));
+        assert_eq!(format.parse(b"1.2500"), Ok(125));
+        assert_eq!(format.parse(b"1.255"), Err(DecimalError::Rounding));
+        assert_eq!(format.parse(b"1.2.3"), Err(DecimalError::InvalidDigit));
+        assert_eq!(format.parse(b"-"), Err(DecimalError::InvalidDigit));
+        assert_eq!(format.parse(b"1,000.00"), Err(DecimalError::InvalidDigit));
+        assert_eq!(format.parse(b""), Err(DecimalError::Empty));
+
+        let max = "9".repeat(36);
+        assert_eq!(format.parse(max.as_bytes()), Ok(10i128.pow(38) - 100));
+        assert_eq!(
+            format.parse(format!("{}9", max).as_bytes()),
+            Err(DecimalError::Overflow)
+        );
+
+        let narrow = DecimalFormat {
+            precision: 5,
+            scale: 2,
+            ..DecimalFormat::default()
+        };
+        assert_eq!(narrow.parse(b"999.99"), Ok(99999));
+        assert_eq!(narrow.parse(b"1000"), Err(DecimalError::Overflow));
+    }
+
+    #[test]
+    fn test_separators() {
+        let european = DecimalFormat {
+            scale: 2,
+            decimal: b',',
+            thousands: Some(b'.'),
+            ..DecimalFormat::default()
+        };
+        assert_eq!(european.parse(b"1.234.567,89"), Ok(123_456_789));
+        assert_eq!(european.parse(b"-12,5"), Ok(-1250));
+        assert_eq!(european.parse(b"999"), Ok(99900));
+        for bad in [
+            &b"1.23,00"[..],
+            b"1234.567",
+            b".123",
+            b"1..234",
+            b"1.234.",
+            b"12,3,4",
+        ] {
+            assert_eq!(
+                european.parse(bad),
+                Err(DecimalError::InvalidDigit),
+                "{:?}",
+                bad
+            );
+        }
+
+        let us = DecimalFormat {
+            scale: 0,
+            thousands: Some(b','),
+            ..DecimalFormat::default()
+        };
+        assert_eq!(us.parse(b"1,000,000"), Ok(1_000_000));
+        assert_eq!(us.parse(b"1,000.4"), Err(DecimalError::Rounding));
+    }
+
+    #[test]
+    fn test_format_decimal() {
+        assert_eq!(format_decimal(1230, 2), "12.30");
+        assert_eq!(format_decimal(-5, 2), "-0.05");
+        assert_eq!(format_decimal(42, 0), "42");
+        assert_eq!(format_decimal(i128::MIN + 1, 38).len(), 41);
+    }
+}
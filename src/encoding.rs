@@ -0,0 +1,232 @@
+//! Input encoding detection and transcoding to UTF-8
+//!
+//! The parser works on UTF-8 (or any ASCII-compatible) bytes. Files exported
+//! as UTF-16 or in a single-byte Western encoding are converted up front so
+//! the SIMD pass sees ASCII delimiters at byte positions.
+
+/// Text encodings recognized by the loader
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Encoding {
+    Utf8,
+    Utf16Le,
+    Utf16Be,
+    Latin1,
+    Windows1252,
+}
+
+/// The encoding of an input and whether it started with a byte order mark
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct DetectedEncoding {
+    pub encoding: Encoding,
+    pub bom: bool,
+}
+
+const UTF8_BOM: &[u8] = &[0xEF, 0xBB, 0xBF];
+const UTF16LE_BOM: &[u8] = &[0xFF, 0xFE];
+const UTF16BE_BOM: &[u8] = &[0xFE, 0xFF];
+
+/// Number of leading bytes inspected by the UTF-16 heuristic
+const SNIFF_LEN: usize = 1024;
+
+/// Windows-1252 code points for bytes 0x80..=0x9F; the five unassigned
+/// bytes map to the matching C1 control, as browsers do
+const WINDOWS_1252_HIGH: [u16; 32] = [
+    0x20AC, 0x0081, 0x201A, 0x0192, 0x201E, 0x2026, 0x2020, 0x2021, 0x02C6, 0x2030, 0x0160, 0x2039,
+    0x0152, 0x008D, 0x017D, 0x008F, 0x0090, 0x2018, 0x2019, 0x201C, 0x201D, 0x2022, 0x2013, 0x2014,
+    0x02DC, 0x2122, 0x0161, 0x203A, 0x0153, 0x009D, 0x017E, 0x0178,
+];
+
+impl Encoding {
+    /// Get the byte order mark this encoding writes
+    pub fn bom(&self) -> &'static [u8] {
+        match self {
+            Encoding::Utf8 => UTF8_BOM,
+            Encoding::Utf16Le => UTF16LE_BOM,
+            Encoding::Utf16Be => UTF16BE_BOM,
+            Encoding::Latin1 | Encoding::Windows1252 => &[],
+        }
+    }
+}
+
+/// Detect a byte order mark at the start of `data`
+pub fn detect_bom(data: &[u8]) -> Option<Encoding> {
+    if data.starts_with(UTF8_BOM) {
+        Some(Encoding::Utf8)
+    } else if data.starts_with(UTF16LE_BOM) {
+        Some(Encoding::Utf16Le)
+    } else if data.starts_with(UTF16BE_BOM) {
+        Some(Encoding::Utf16Be)
+    } else {
+        None
+    }
+}
+
+/// Guess the encoding of `data`
+///
+/// A byte order mark wins. Without one, UTF-16 is recognized by NUL bytes in
+/// every other position of mostly-ASCII text, then valid UTF-8 is reported as
+/// such. Anything else is assumed to be Windows-1252, which agrees with
+/// ISO-8859-1 on every printable character.
+pub fn detect_encoding(data: &[u8]) -> DetectedEncoding {
+    if let Some(encoding) = detect_bom(data) {
+        return DetectedEncoding {
+            encoding,
+            bom: true,
+        };
+    }
+
+    let encoding = if let Some(utf16) = sniff_utf16(data) {
+        utf16
+    } else if std::str::from_utf8(data).is_ok() {
+        Encoding::Utf8
+    } else {
+        Encoding::Windows1252
+    };
+
+    DetectedEncoding {
+        encoding,
+        bom: false,
+    }
+}
+
+fn sniff_utf16(data: &[u8]) -> Option<Encoding> {
+    let sample = &data[..data.len().min(SNIFF_LEN) & !1];
+    if sample.is_empty() {
+        return None;
+    }
+
+    let pairs = sample.len() / 2;
+    let (mut even_nul, mut odd_nul) = (0, 0);
+    for pair in sample.chunks_exact(2) {
+        even_nul += usize::from(pair[0] == 0);
+        odd_nul += usize::from(pair[1] == 0);
+    }
+
+    if odd_nul * 2 > pairs && even_nul == 0 {
+        Some(Encoding::Utf16Le)
+    } else if even_nul * 2 > pairs && odd_nul == 0 {
+        Some(Encoding::Utf16Be)
+    } else {
+        None
+    }
+}
+
+/// Convert `data` from `encoding` to UTF-8
+///
+/// A leading byte order mark is dropped. Unpaired UTF-16 surrogates and a
+/// dangling odd byte become U+FFFD. UTF-8 input is copied as is.
+pub fn transcode_to_utf8(data: &[u8], encoding: Encoding) -> Vec<u8> {
+    let data = data.strip_prefix(encoding.bom()).unwrap_or(data);
+
+    match encoding {
+        Encoding::Utf8 => data.to_vec(),
+        Encoding::Utf16Le => decode_utf16(data, u16::from_le_bytes),
+        Encoding::Utf16Be => decode_utf16(data, u16::from_be_bytes),
+        Encoding::Latin1 => decode_single_byte(data, |b| b as u32),
+        Encoding::Windows1252 => decode_single_byte(data, |b| match b {
+            0x80..=0x9F => WINDOWS_1252_HIGH[(b - 0x80) as usize] as u32,
+            _ => b as u32,
+        }),
+    }
+}
+
+fn decode_utf16(data: &[u8], unit: fn([u8; 2]) -> u16) -> Vec<u8> {
+    let mut out = Vec::with_capacity(data.len());
+    let units = data.chunks_exact(2).map(|pair| unit([pair[0], pair[1]]));
+    let mut tmp = [0u8; 4];
+
+    for ch in char::decode_utf16(units) {
+        let ch = ch.unwrap_or(char::REPLACEMENT_CHARACTER);
+        out.extend_from_slice(ch.encode_utf8(&mut tmp).as_bytes());
+    }
+    if data.len() % 2 == 1 {
+        out.extend_from_slice(char::REPLACEMENT_CHARACTER.encode_utf8(&mut tmp).as_bytes());
+    }
+    out
+}
+
+fn decode_single_byte(data: &[u8], code_point: impl Fn(u8) -> u32) -> Vec<u8> {
+    let mut out = Vec::with_capacity(data.len() + data.len() / 8);
+    let mut tmp = [0u8; 4];
+
+    for &b in data {
+        if b.is_ascii() {
+            out.push(b);
+        } else {
+            let ch = char::from_u32(code_point(b)).unwrap_or(char::REPLACEMENT_CHARACTER);
+            out.extend_from_slice(ch.encode_utf8(&mut tmp).as_bytes());
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn utf16le(s: &str) -> Vec<u8> {
+        s.encode_utf16().flat_map(|u| u.to_le_bytes()).collect()
+    }
+
+    fn utf16be(s: &str) -> Vec<u8> {
+        s.encode_utf16().flat_map(|u| u.to_be_bytes()).collect()
+    }
+
+    #[test]
+    fn test_detect_bom() {
+        assert_eq!(detect_bom(b"\xEF\xBB\xBFa,b"), Some(Encoding::Utf8));
+        assert_eq!(detect_bom(b"\xFF\xFEa\x00"), Some(Encoding::Utf16Le));
+        assert_eq!(detect_bom(b"\xFE\xFF\x00a"), Some(Encoding::Utf16Be));
+        assert_eq!(detect_bom(b"a,b"), None);
+    }
+
+    #[test]
+    fn test_detect_encoding() {
+        let detected = detect_encoding(&utf16le("name,city\nJosé,Zürich\n"));
+        assert_eq!(detected.encoding, Encoding::Utf16Le);
+        assert!(!detected.bom);
+
+        let detected = detect_encoding(&utf16be("name,city\n"));
+        assert_eq!(detected.encoding, Encoding::Utf16Be);
+
+        let detected = detect_encoding("name,city\nJosé,Zürich\n".as_bytes());
+        assert_eq!(detected.encoding, Encoding::Utf8);
+
+        let detected = detect_encoding(b"name\nJos\xE9\n");
+        assert_eq!(detected.encoding, Encoding::Windows1252);
+    }
+
+    #[test]
+    fn test_transcode() {
+        let mut data = UTF16LE_BOM.to_vec();
+        data.extend(utf16le("a,\"€\"\n😀,b\n"));
+        assert_eq!(
+            transcode_to_utf8(&data, Encoding::Utf16Le),
+            "a,\"€\"\n😀,b\n".as_bytes()
+        );
+
+        assert_eq!(
+            transcode_to_utf8(&utf16be("x,ß"), Encoding::Utf16Be),
+            "x,ß".as_bytes()
+        );
+        assert_eq!(transcode_to_utf8(b"\xEF\xBB\xBFid", Encoding::Utf8), b"id");
+        assert_eq!(
+            transcode_to_utf8(b"caf\xE9 \x80", Encoding::Latin1),
+            "café \u{80}".as_bytes()
+        );
+        assert_eq!(
+            transcode_to_utf8(b"caf\xE9 \x80", Encoding::Windows1252),
+            "café €".as_bytes()
+        );
+    }
+
+    #[test]
+    fn test_transcode_invalid_utf16() {
+        // Unpaired high surrogate followed by an odd trailing byte
+        let data = [0x00, 0xD8, b'a', 0x00, b'b'];
+        assert_eq!(
+            transcode_to_utf8(&data, Encoding::Utf16Le),
+            "\u{FFFD}a\u{FFFD}".as_bytes()
+        );
+    }
+}
@@ -0,0 +1,192 @@
+//! Error type shared by the loading, allocation and parsing entry points
+
+use std::alloc::LayoutError;
+use std::fmt;
+use std::io;
+
+/// Result alias using the crate error type
+pub type Result<T> = std::result::Result<T, Error>;
+
+/// Errors reported by simdcsv
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum Error {
+    /// Reading the input failed
+    Io { context: String, source: io::Error },
+    /// The requested buffer size does not form a valid allocation layout
+    Layout { size: usize, source: LayoutError },
+    /// The allocator could not provide the requested buffer
+    Allocation { size: usize },
+    /// The buffer handed to the parser lacks zeroed padding from `offset` on
+    InvalidPadding { offset: usize },
+    /// The input is not well-formed CSV
+    Parse { offset: usize, kind: ParseErrorKind },
+    /// A selected column name is not in the header
+    UnknownColumn { name: String },
+    /// A date pattern has an unknown or unfinished `%` directive
+    InvalidPattern { pattern: String },
+    /// A record does not fit the type it is deserialized into
+    Deserialize {
+        /// Byte offset of the record
+        offset: usize,
+        /// Header name or position of the field at fault, if known
+        field: Option<String>,
+        message: String,
+    },
+    /// Records could not be converted to Arrow arrays
+    Arrow { message: String },
+    /// The input exceeds a limit of the parser
+    LimitExceeded {
+        limit: Limit,
+        max: usize,
+        offset: usize,
+    },
+}
+
+/// The ways an input can fail to parse
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum ParseErrorKind {
+    /// A quoted field is still open at the end of the input
+    UnterminatedQuote,
+    /// The input is not valid UTF-8
+    InvalidUtf8,
+    /// A record has a different number of fields than the first record
+    FieldCount {
+        /// 1-based line the record starts on
+        line: usize,
+        expected: usize,
+        actual: usize,
+    },
+}
+
+/// Limits the parser enforces on its input
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum Limit {
+    /// Input length in bytes; indexes are stored as `u32`
+    InputLength,
+    /// Length of one field in bytes
+    FieldLength,
+    /// Number of fields in one record
+    FieldsPerRecord,
+    /// Number of records
+    Records,
+    /// Memory used by the separator indexes in bytes
+    IndexMemory,
+}
+
+impl Error {
+    pub(crate) fn io(context: impl Into<String>, source: io::Error) -> Self {
+        Error::Io {
+            context: context.into(),
+            source,
+        }
+    }
+}
+
+impl fmt::Display for ParseErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ParseErrorKind::UnterminatedQuote => write!(f, "unterminated quoted field"),
+            ParseErrorKind::InvalidUtf8 => write!(f, "invalid UTF-8"),
+            ParseErrorKind::FieldCount {
+                line,
+                expected,
+                actual,
+            } => write!(
+                f,
+                "line {} has {} fields, expected {}",
+                line, actual, expected
+            ),
+        }
+    }
+}
+
+impl fmt::Display for Limit {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Limit::InputLength => write!(f, "input length"),
+            Limit::FieldLength => write!(f, "field length"),
+            Limit::FieldsPerRecord => write!(f, "field count"),
+            Limit::Records => write!(f, "record count"),
+            Limit::IndexMemory => write!(f, "index memory"),
+        }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Io { context, source } => write!(f, "{}: {}", context, source),
+            Error::Layout { size, source } => {
+                write!(f, "invalid layout for {} bytes: {}", size, source)
+            }
+            Error::Allocation { size } => write!(f, "failed to allocate {} bytes", size),
+            Error::InvalidPadding { offset } => {
+                write!(f, "padding is missing or not zeroed at byte {}", offset)
+            }
+            Error::Parse { offset, kind } => write!(f, "{} at byte {}", kind, offset),
+            Error::UnknownColumn { name } => write!(f, "unknown column '{}'", name),
+            Error::InvalidPattern { pattern } => write!(f, "invalid date pattern '{}'", pattern),
+            Error::Deserialize {
+                offset,
+                field: Some(field),
+                message,
+            } => write!(
+                f,
+                "field {} of the record at byte {}: {}",
+                field, offset, message
+            ),
+            Error::Deserialize {
+                offset, message, ..
+            } => write!(f, "record at byte {}: {}", offset, message),
+            Error::Arrow { message } => write!(f, "arrow conversion failed: {}", message),
+            Error::LimitExceeded { limit, max, offset } => {
+                write!(
+                    f,
+                    "{} exceeds the maximum of {} at byte {}",
+                    limit, max, offset
+                )
+            }
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Io { source, .. } => Some(source),
+            Error::Layout { source, .. } => Some(source),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::error::Error as _;
+
+    #[test]
+    fn test_display_and_source() {
+        let err = Error::io(
+            "could not open file 'x.csv'",
+            io::Error::new(io::ErrorKind::NotFound, "missing"),
+        );
+        assert_eq!(err.to_string(), "could not open file 'x.csv': missing");
+        assert!(err.source().is_some());
+
+        let err = Error::Parse {
+            offset: 12,
+            kind: ParseErrorKind::UnterminatedQuote,
+        };
+        assert_eq!(err.to_string(), "unterminated quoted field at byte 12");
+        assert!(err.source().is_none());
+
+        let err = Error::UnknownColumn {
+            name: "x".to_string(),
+        };
+        assert_eq!(err.to_string(), "unknown column 'x'");
+    }
+}
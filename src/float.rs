@@ -0,0 +1,383 @@
+//! Float conversion of field bytes
+//!
+//! The field is read into a decimal mantissa of at most 19 digits and a
+//! power of ten. Small values take the exact floating-point fast path;
+//! the rest go through the Eisel-Lemire algorithm, which multiplies the
+//! mantissa by a 128-bit approximation of the power of ten and rounds
+//! correctly unless the product lands too close to a halfway point. Those
+//! rare inputs, and mantissas truncated from more than 19 digits where the
+//! truncation changes the result, fall back to the standard library.
+
+use crate::float_table::{LARGEST_POWER_OF_FIVE, POWER_OF_FIVE_128, SMALLEST_POWER_OF_FIVE};
+use crate::numeric::NumberError;
+
+const MANTISSA_EXPLICIT_BITS: i32 = 52;
+const MINIMUM_EXPONENT: i32 = -1023;
+const INFINITE_POWER: i32 = 0x7FF;
+const MIN_EXPONENT_ROUND_TO_EVEN: i64 = -4;
+const MAX_EXPONENT_ROUND_TO_EVEN: i64 = 23;
+const MAX_EXPONENT_FAST_PATH: i64 = 22;
+const MAX_EXPONENT_DISGUISED_FAST_PATH: i64 = 37;
+const MAX_MANTISSA_FAST_PATH: u64 = 2 << MANTISSA_EXPLICIT_BITS;
+
+/// Exponents beyond this are clamped; they overflow or underflow anyway
+const EXPONENT_CLAMP: i64 = 0x10000;
+
+#[rustfmt::skip]
+const POW10_F64: [f64; 23] = [
+    1e0, 1e1, 1e2, 1e3, 1e4, 1e5, 1e6, 1e7, 1e8, 1e9, 1e10, 1e11,
+    1e12, 1e13, 1e14, 1e15, 1e16, 1e17, 1e18, 1e19, 1e20, 1e21, 1e22,
+];
+
+/// Convert `field` to `f64` with `.` as the decimal separator
+pub fn parse_f64(field: &[u8]) -> Result<f64, NumberError> {
+    parse_f64_with(field, b'.')
+}
+
+/// Convert `field` to `f64` with `decimal` as the decimal separator
+///
+/// Accepts an optional sign, digits with at most one separator, an
+/// optional `e` exponent, and `inf`, `infinity` or `nan` in any case.
+/// Values too large for `f64` become infinite, as with `str::parse`.
+pub fn parse_f64_with(field: &[u8], decimal: u8) -> Result<f64, NumberError> {
+    let (negative, rest) = match field {
+        [] => return Err(NumberError::Empty),
+        [b'-', rest @ ..] => (true, rest),
+        [b'+', rest @ ..] => (false, rest),
+        rest => (false, rest),
+    };
+
+    let value = match parse_decimal(rest, decimal) {
+        Some(number) => number.to_f64().unwrap_or_else(|| fallback(rest, decimal)),
+        None => parse_special(rest).ok_or(NumberError::InvalidDigit)?,
+    };
+    Ok(if negative { -value } else { value })
+}
+
+fn parse_special(rest: &[u8]) -> Option<f64> {
+    if rest.eq_ignore_ascii_case(b"inf") || rest.eq_ignore_ascii_case(b"infinity") {
+        Some(f64::INFINITY)
+    } else if rest.eq_ignore_ascii_case(b"nan") {
+        Some(f64::NAN)
+    } else {
+        None
+    }
+}
+
+/// Convert an already validated unsigned decimal with the standard library
+#[cold]
+fn fallback(rest: &[u8], decimal: u8) -> f64 {
+    let text: String = rest
+        .iter()
+        .map(|&b| if b == decimal { '.' } else { b as char })
+        .collect();
+    text.parse().unwrap_or(f64::NAN)
+}
+
+/// `mantissa * 10^exponent`, with `many_digits` set if digits were dropped
+#[derive(Debug, Default)]
+struct Decimal {
+    mantissa: u64,
+    exponent: i64,
+    digits: usize,
+    many_digits: bool,
+}
+
+enum Digit {
+    Kept,
+    LeadingZero,
+    Dropped,
+}
+
+impl Decimal {
+    #[inline]
+    fn push(&mut self, digit: u8) -> Digit {
+        if self.digits == 0 && digit == 0 {
+            Digit::LeadingZero
+        } else if self.digits < 19 {
+            self.mantissa = self.mantissa * 10 + digit as u64;
+            self.digits += 1;
+            Digit::Kept
+        } else {
+            self.many_digits = self.many_digits || digit != 0;
+            Digit::Dropped
+        }
+    }
+
+    fn to_f64(&self) -> Option<f64> {
+        if let Some(value) = self.fast_path() {
+            return Some(value);
+        }
+        let fp = compute_float(self.exponent, self.mantissa);
+        if fp.e < 0 {
+            return None;
+        }
+        // The true mantissa lies between the truncated one and the next
+        if self.many_digits && fp != compute_float(self.exponent, self.mantissa + 1) {
+            return None;
+        }
+        Some(f64::from_bits(
+            fp.f | (fp.e as u64) << MANTISSA_EXPLICIT_BITS,
+        ))
+    }
+
+    /// Exact when both the mantissa and the power of ten are exact floats
+    fn fast_path(&self) -> Option<f64> {
+        if self.many_digits || self.mantissa > MAX_MANTISSA_FAST_PATH {
+            return None;
+        }
+        match self.exponent {
+            e if (-MAX_EXPONENT_FAST_PATH..0).contains(&e) => {
+                Some(self.mantissa as f64 / POW10_F64[-e as usize])
+            }
+            e if (0..=MAX_EXPONENT_FAST_PATH).contains(&e) => {
+                Some(self.mantissa as f64 * POW10_F64[e as usize])
+            }
+            e if e <= MAX_EXPONENT_DISGUISED_FAST_PATH && e > 0 => {
+                let shift = 10u64.pow((e - MAX_EXPONENT_FAST_PATH) as u32);
+                let mantissa = self.mantissa.checked_mul(shift)?;
+                (mantissa <= MAX_MANTISSA_FAST_PATH)
+                    .then(|| mantissa as f64 * POW10_F64[MAX_EXPONENT_FAST_PATH as usize])
+            }
+            _ => None,
+        }
+    }
+}
+
+/// Read unsigned `digits[.digits][e[+-]digits]`; `None` if malformed
+fn parse_decimal(bytes: &[u8], decimal: u8) -> Option<Decimal> {
+    let mut number = Decimal::default();
+    let mut any_digit = false;
+    let mut i = 0;
+
+    while let Some(&b) = bytes.get(i).filter(|b| b.is_ascii_digit()) {
+        if let Digit::Dropped = number.push(b - b'0') {
+            number.exponent += 1;
+        }
+        any_digit = true;
+        i += 1;
+    }
+    if bytes.get(i) == Some(&decimal) {
+        i += 1;
+        while let Some(&b) = bytes.get(i).filter(|b| b.is_ascii_digit()) {
+            if let Digit::Kept | Digit::LeadingZero = number.push(b - b'0') {
+                number.exponent -= 1;
+            }
+            any_digit = true;
+            i += 1;
+        }
+    }
+    if !any_digit {
+        return None;
+    }
+
+    if matches!(bytes.get(i), Some(b'e' | b'E')) {
+        i += 1;
+        let negative = match bytes.get(i) {
+            Some(b'-') => {
+                i += 1;
+                true
+            }
+            Some(b'+') => {
+                i += 1;
+                false
+            }
+            _ => false,
+        };
+        let start = i;
+        let mut exponent = 0i64;
+        while let Some(&b) = bytes.get(i).filter(|b| b.is_ascii_digit()) {
+            exponent = (exponent * 10 + (b - b'0') as i64).min(EXPONENT_CLAMP);
+            i += 1;
+        }
+        if i == start {
+            return None;
+        }
+        number.exponent += if negative { -exponent } else { exponent };
+    }
+
+    (i == bytes.len()).then_some(number)
+}
+
+/// Biased exponent `e` and explicit mantissa bits `f`; `e < 0` means undecided
+#[derive(Debug, PartialEq, Eq)]
+struct BiasedFp {
+    f: u64,
+    e: i32,
+}
+
+impl BiasedFp {
+    fn zero_pow2(e: i32) -> Self {
+        Self { f: 0, e }
+    }
+}
+
+/// Round `w * 10^q` to the nearest `f64`
+fn compute_float(q: i64, mut w: u64) -> BiasedFp {
+    if w == 0 || q < SMALLEST_POWER_OF_FIVE {
+        return BiasedFp::zero_pow2(0);
+    }
+    if q > LARGEST_POWER_OF_FIVE {
+        return BiasedFp::zero_pow2(INFINITE_POWER);
+    }
+    let lz = w.leading_zeros() as i32;
+    w <<= lz;
+    let (lo, hi) = compute_product_approx(q, w, MANTISSA_EXPLICIT_BITS as u32 + 3);
+    // The product is truncated; outside this range the error may matter
+    if lo == u64::MAX && !(-27..=55).contains(&q) {
+        return BiasedFp::zero_pow2(-1);
+    }
+
+    let upperbit = (hi >> 63) as i32;
+    let shift = upperbit + 64 - MANTISSA_EXPLICIT_BITS - 3;
+    let mut mantissa = hi >> shift;
+    let mut power2 = power(q as i32) + upperbit - lz - MINIMUM_EXPONENT;
+
+    if power2 <= 0 {
+        // Subnormal
+        if -power2 + 1 >= 64 {
+            return BiasedFp::zero_pow2(0);
+        }
+        mantissa >>= -power2 + 1;
+        mantissa += mantissa & 1;
+        mantissa >>= 1;
+        let e = (mantissa >= 1 << MANTISSA_EXPLICIT_BITS) as i32;
+        return BiasedFp { f: mantissa, e };
+    }
+
+    // An exact halfway product rounds to even instead of up
+    if lo <= 1
+        && (MIN_EXPONENT_ROUND_TO_EVEN..=MAX_EXPONENT_ROUND_TO_EVEN).contains(&q)
+        && mantissa & 3 == 1
+        && mantissa << shift == hi
+    {
+        mantissa &= !1;
+    }
+    mantissa += mantissa & 1;
+    mantissa >>= 1;
+    if mantissa >= 2 << MANTISSA_EXPLICIT_BITS {
+        mantissa = 1 << MANTISSA_EXPLICIT_BITS;
+        power2 += 1;
+    }
+    mantissa &= !(1 << MANTISSA_EXPLICIT_BITS);
+    if power2 >= INFINITE_POWER {
+        return BiasedFp::zero_pow2(INFINITE_POWER);
+    }
+    BiasedFp {
+        f: mantissa,
+        e: power2,
+    }
+}
+
+/// `floor(q * log2(10)) + 63` for the `q` range of the table
+#[inline]
+fn power(q: i32) -> i32 {
+    (q.wrapping_mul(152_170 + 65536) >> 16) + 63
+}
+
+#[inline]
+fn full_multiplication(a: u64, b: u64) -> (u64, u64) {
+    let r = a as u128 * b as u128;
+    (r as u64, (r >> 64) as u64)
+}
+
+/// The high 128 bits of `w * 5^q`, exact in the `precision` top bits
+fn compute_product_approx(q: i64, w: u64, precision: u32) -> (u64, u64) {
+    let mask = u64::MAX >> precision;
+    let (hi5, lo5) = POWER_OF_FIVE_128[(q - SMALLEST_POWER_OF_FIVE) as usize];
+    let (mut first_lo, mut first_hi) = full_multiplication(w, hi5);
+    if first_hi & mask == mask {
+        // Bring in the low half of the power for the bits that decide rounding
+        let (_, second_hi) = full_multiplication(w, lo5);
+        first_lo = first_lo.wrapping_add(second_hi);
+        if second_hi > first_lo {
+            first_hi += 1;
+        }
+    }
+    (first_lo, first_hi)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn check(text: &str) {
+        let expected: f64 = text.parse().unwrap();
+        let actual = parse_f64(text.as_bytes()).unwrap();
+        assert_eq!(actual.to_bits(), expected.to_bits(), "{}", text);
+    }
+
+    #[test]
+    fn test_parse_floats() {
+        for text in [
+            "0",
+            "-0",
+            "1.5",
+            "+.5",
+            "7.",
+            "3.14159265358979323846264338327950288",
+            "9007199254740993",
+            "2.2250738585072011e-308",
+            "2.2250738585072014e-308",
+            "4.9e-324",
+            "2.4e-324",
+            "1.7976931348623157e308",
+            "1.7976931348623159e308",
+            "1e309",
+            "1e-400",
+            "0.000000000000000000000000000000000001234",
+            "123456789012345678901234567890e-10",
+            "1e23",
+            "8.98846567431158e307",
+            "00001234.5678000",
+            "1E+5",
+        ] {
+            check(text);
+        }
+        assert!(parse_f64(b"NaN").unwrap().is_nan());
+        assert_eq!(parse_f64(b"-Infinity"), Ok(f64::NEG_INFINITY));
+        assert_eq!(parse_f64_with(b"-1,25e2", b','), Ok(-125.0));
+    }
+
+    #[test]
+    fn test_parse_float_errors() {
+        assert_eq!(parse_f64(b""), Err(NumberError::Empty));
+        for text in [
+            &b"."[..],
+            b"-",
+            b"1e",
+            b"1e+",
+            b"1.2.3",
+            b"1,5",
+            b" 1",
+            b"0x10",
+            b"infinit",
+        ] {
+            assert_eq!(
+                parse_f64(text),
+                Err(NumberError::InvalidDigit),
+                "{:?}",
+                text
+            );
+        }
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let mut state = 0x9E37_79B9_7F4A_7C15u64;
+        for _ in 0..20_000 {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            let value = f64::from_bits(state);
+            if !value.is_finite() {
+                continue;
+            }
+            for text in [format!("{}", value), format!("{:e}", value)] {
+                check(&text);
+            }
+            // Digits beyond what the value needs take the truncated path
+            check(&format!("{:.25e}", value));
+        }
+    }
+}
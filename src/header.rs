@@ -0,0 +1,326 @@
+//! Header names: normalization and sanity checks
+
+use crate::parser::ParsedCsv;
+use crate::record::Record;
+use std::borrow::Borrow;
+use std::collections::{HashMap, HashSet};
+use std::hash::{Hash, Hasher};
+
+/// How header names are cleaned up by `ParsedCsv::header_names`
+#[derive(Clone, Debug, Default)]
+pub struct HeaderNormalization {
+    /// Strip leading and trailing whitespace
+    pub trim: bool,
+    /// Convert names to lowercase
+    pub lowercase: bool,
+    /// Rename repeated names to `name_2`, `name_3`, ... in column order
+    pub dedupe: bool,
+}
+
+/// A problem with the header names
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum HeaderIssue {
+    /// Column `column` has an empty name
+    Empty { column: usize },
+    /// Column `column` repeats the name of column `first`
+    Duplicate { column: usize, first: usize },
+}
+
+/// Decode the header fields of `record`, unquoted and normalized
+///
+/// Fields that are not UTF-8 are decoded lossily.
+pub fn normalize_headers(record: &Record<'_>, options: &HeaderNormalization) -> Vec<String> {
+    let mut names: Vec<String> = (0..record.len())
+        .map(|i| {
+            let field = record.get_unquoted(i).unwrap_or_default();
+            let mut name = String::from_utf8_lossy(&field).into_owned();
+            if options.trim {
+                name = name.trim().to_string();
+            }
+            if options.lowercase {
+                name = name.to_lowercase();
+            }
+            name
+        })
+        .collect();
+
+    if options.dedupe {
+        dedupe(&mut names);
+    }
+    names
+}
+
+/// Give every repeated non-empty name a numeric suffix that is not taken
+fn dedupe(names: &mut [String]) {
+    let mut taken: HashSet<String> = names.iter().cloned().collect();
+    let mut seen = HashSet::new();
+    let mut next_suffix: HashMap<String, usize> = HashMap::new();
+
+    for name in names.iter_mut() {
+        if name.is_empty() || seen.insert(name.clone()) {
+            continue;
+        }
+        let suffix = next_suffix.entry(name.clone()).or_insert(2);
+        let renamed = loop {
+            let candidate = format!("{}_{}", name, suffix);
+            *suffix += 1;
+            if taken.insert(candidate.clone()) {
+                break candidate;
+            }
+        };
+        seen.insert(renamed.clone());
+        *name = renamed;
+    }
+}
+
+/// Find empty and duplicate names, in column order
+pub fn header_issues<S: AsRef<str>>(names: &[S]) -> Vec<HeaderIssue> {
+    let mut issues = Vec::new();
+    let mut first_seen: HashMap<&str, usize> = HashMap::new();
+
+    for (column, name) in names.iter().enumerate() {
+        let name = name.as_ref();
+        if name.is_empty() {
+            issues.push(HeaderIssue::Empty { column });
+            continue;
+        }
+        if let Some(&first) = first_seen.get(name) {
+            issues.push(HeaderIssue::Duplicate { column, first });
+        } else {
+            first_seen.insert(name, column);
+        }
+    }
+    issues
+}
+
+/// Column positions by header name, built once for O(1) lookups per row
+///
+/// A repeated name maps to its first column.
+#[derive(Clone, Debug)]
+pub struct HeaderMap {
+    names: Vec<String>,
+    index: NameIndex,
+}
+
+/// Name lookup, exact or without regard to ASCII case
+#[derive(Clone, Debug)]
+enum NameIndex {
+    Exact(HashMap<String, usize>),
+    Caseless(HashMap<CaselessKey, usize>),
+}
+
+/// A header name that hashes and compares without regard to ASCII case,
+/// so lookups need no lowercased copy of the query
+#[derive(Debug)]
+#[repr(transparent)]
+struct Caseless(str);
+
+impl Caseless {
+    fn new(name: &str) -> &Caseless {
+        // SAFETY: `Caseless` is a `repr(transparent)` wrapper around `str`
+        unsafe { &*(name as *const str as *const Caseless) }
+    }
+}
+
+impl PartialEq for Caseless {
+    fn eq(&self, other: &Self) -> bool {
+        self.0.eq_ignore_ascii_case(&other.0)
+    }
+}
+
+impl Eq for Caseless {}
+
+impl Hash for Caseless {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        for b in self.0.bytes() {
+            state.write_u8(b.to_ascii_lowercase());
+        }
+        // Terminate like `str` does, so names are prefix-free
+        state.write_u8(0xff);
+    }
+}
+
+/// Owned key of the caseless index
+#[derive(Clone, Debug)]
+struct CaselessKey(String);
+
+impl Borrow<Caseless> for CaselessKey {
+    fn borrow(&self) -> &Caseless {
+        Caseless::new(&self.0)
+    }
+}
+
+impl PartialEq for CaselessKey {
+    fn eq(&self, other: &Self) -> bool {
+        Caseless::new(&self.0) == Caseless::new(&other.0)
+    }
+}
+
+impl Eq for CaselessKey {}
+
+impl Hash for CaselessKey {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        Caseless::new(&self.0).hash(state)
+    }
+}
+
+impl HeaderMap {
+    /// Map the unquoted names of `header`, optionally ignoring ASCII case
+    pub fn new(header: &Record<'_>, ignore_case: bool) -> Self {
+        let names = normalize_headers(header, &HeaderNormalization::default());
+        let index = if ignore_case {
+            let mut index = HashMap::with_capacity(names.len());
+            for (i, name) in names.iter().enumerate() {
+                index.entry(CaselessKey(name.clone())).or_insert(i);
+            }
+            NameIndex::Caseless(index)
+        } else {
+            let mut index = HashMap::with_capacity(names.len());
+            for (i, name) in names.iter().enumerate() {
+                index.entry(name.clone()).or_insert(i);
+            }
+            NameIndex::Exact(index)
+        };
+        Self { names, index }
+    }
+
+    /// Get the column of `name`
+    #[inline]
+    pub fn index_of(&self, name: &str) -> Option<usize> {
+        match &self.index {
+            NameIndex::Exact(index) => index.get(name).copied(),
+            NameIndex::Caseless(index) => index.get(Caseless::new(name)).copied(),
+        }
+    }
+
+    /// Get the header names in column order
+    pub fn names(&self) -> &[String] {
+        &self.names
+    }
+
+    /// Get the number of columns in the header
+    pub fn len(&self) -> usize {
+        self.names.len()
+    }
+
+    /// Check if the header has no columns
+    pub fn is_empty(&self) -> bool {
+        self.names.is_empty()
+    }
+}
+
+impl<'a> Record<'a> {
+    /// Get the raw bytes of the field in column `name`
+    #[inline]
+    pub fn get_by_name(&self, headers: &HeaderMap, name: &str) -> Option<&'a [u8]> {
+        self.get(headers.index_of(name)?)
+    }
+}
+
+impl ParsedCsv {
+    /// Map the header names of `buf` to columns, if the input has a header
+    pub fn header_map(&self, buf: &[u8], ignore_case: bool) -> Option<HeaderMap> {
+        self.headers(buf)
+            .map(|record| HeaderMap::new(&record, ignore_case))
+    }
+
+    /// Get the normalized header names of `buf`, if the input has a header
+    pub fn header_names(&self, buf: &[u8], options: &HeaderNormalization) -> Option<Vec<String>> {
+        self.headers(buf)
+            .map(|record| normalize_headers(&record, options))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::memory::Padded;
+    use crate::parser::{parse_csv_with, ParseOptions};
+
+    fn parse(data: &[u8]) -> ParsedCsv {
+        let options = ParseOptions {
+            has_header: true,
+            ..ParseOptions::default()
+        };
+        parse_csv_with(Padded::from_slice(data).as_padded(), &options).unwrap()
+    }
+
+    #[test]
+    fn test_headers_and_records() {
+        let data = b"id,name\n1,a\n2,b\n";
+        let pcsv = parse(data);
+        let header: Vec<&[u8]> = pcsv.headers(data).unwrap().iter().collect();
+        assert_eq!(header, vec![&b"id"[..], b"name"]);
+        let ids: Vec<&[u8]> = pcsv.records(data).map(|r| r.get(0).unwrap()).collect();
+        assert_eq!(ids, vec![&b"1"[..], b"2"]);
+
+        let pcsv = parse_csv_with(
+            Padded::from_slice(data).as_padded(),
+            &ParseOptions::default(),
+        )
+        .unwrap();
+        assert!(pcsv.headers(data).is_none());
+        assert_eq!(pcsv.records(data).count(), 3);
+    }
+
+    #[test]
+    fn test_normalization() {
+        let data = b" Id ,\"Name\",name,NAME_2,,\"say \"\"hi\"\"\"\n";
+        let pcsv = parse(data);
+
+        let raw = pcsv
+            .header_names(data, &HeaderNormalization::default())
+            .unwrap();
+        assert_eq!(raw, [" Id ", "Name", "name", "NAME_2", "", "say \"hi\""]);
+
+        let options = HeaderNormalization {
+            trim: true,
+            lowercase: true,
+            dedupe: true,
+        };
+        let names = pcsv.header_names(data, &options).unwrap();
+        assert_eq!(names, ["id", "name", "name_3", "name_2", "", "say \"hi\""]);
+    }
+
+    #[test]
+    fn test_header_map() {
+        let data = b"gameid,\"Qtr\",qtr\nA,1,2\nB,3\n";
+        let pcsv = parse(data);
+
+        let headers = pcsv.header_map(data, false).unwrap();
+        assert_eq!(headers.len(), 3);
+        let rows: Vec<_> = pcsv.records(data).collect();
+        assert_eq!(rows[0].get_by_name(&headers, "gameid"), Some(&b"A"[..]));
+        assert_eq!(rows[0].get_by_name(&headers, "Qtr"), Some(&b"1"[..]));
+        assert_eq!(rows[0].get_by_name(&headers, "qtr"), Some(&b"2"[..]));
+        assert_eq!(rows[1].get_by_name(&headers, "qtr"), None);
+        assert_eq!(rows[0].get_by_name(&headers, "QTR"), None);
+
+        let headers = pcsv.header_map(data, true).unwrap();
+        assert_eq!(headers.index_of("QTR"), Some(1));
+        assert_eq!(headers.index_of("GameID"), Some(0));
+        assert_eq!(headers.index_of("qtr"), Some(1));
+        assert_eq!(headers.index_of("gameidx"), None);
+        assert_eq!(headers.index_of("missing"), None);
+    }
+
+    #[test]
+    fn test_header_issues() {
+        let issues = header_issues(&["a", "", "b", "a", "a", ""]);
+        assert_eq!(
+            issues,
+            [
+                HeaderIssue::Empty { column: 1 },
+                HeaderIssue::Duplicate {
+                    column: 3,
+                    first: 0
+                },
+                HeaderIssue::Duplicate {
+                    column: 4,
+                    first: 0
+                },
+                HeaderIssue::Empty { column: 5 },
+            ]
+        );
+    }
+}
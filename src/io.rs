@@ -1,18 +1,41 @@
 //! I/O utilities for loading CSV files with padding
 
-use crate::memory::{aligned_free, allocate_padded_buffer};
+use crate::encoding::{detect_encoding, transcode_to_utf8, DetectedEncoding, Encoding};
+use crate::error::{Error, Result};
+use crate::memory::{aligned_free, allocate_padded_buffer, Padded};
+use crate::CSV_PADDING;
 use std::fs::File;
 use std::io::Read;
 use std::ptr::NonNull;
+use std::sync::Mutex;
 
 /// A buffer containing file data with padding for safe SIMD operations
 pub struct PaddedBuffer {
     ptr: NonNull<u8>,
     length: usize,
+    capacity: usize,
     padding: usize,
 }
 
+// SAFETY: PaddedBuffer uniquely owns its allocation, like a Box<[u8]>
+unsafe impl Send for PaddedBuffer {}
+unsafe impl Sync for PaddedBuffer {}
+
 impl PaddedBuffer {
+    /// Allocate a zeroed buffer holding `length` data bytes
+    ///
+    /// `padding` is raised to `CSV_PADDING` if smaller.
+    pub fn new(length: usize, padding: usize) -> Result<Self> {
+        let padding = padding.max(CSV_PADDING);
+        let ptr = allocate_padded_buffer(length, padding)?;
+        Ok(Self {
+            ptr,
+            length,
+            capacity: length,
+            padding,
+        })
+    }
+
     /// Get a slice view of the data (excluding padding)
     #[inline(always)]
     pub fn data(&self) -> &[u8] {
@@ -31,52 +54,204 @@ impl PaddedBuffer {
         self.length == 0
     }
 
+    /// Get the largest data length this buffer can hold without reallocating
+    #[inline(always)]
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    /// Get a mutable slice view of the data (excluding padding)
+    #[inline(always)]
+    pub fn data_mut(&mut self) -> &mut [u8] {
+        unsafe { std::slice::from_raw_parts_mut(self.ptr.as_ptr(), self.length) }
+    }
+
+    /// Change the data length of a buffer being reused
+    ///
+    /// The bytes following the new length are zeroed so the padding
+    /// guarantee holds. The data bytes keep whatever the previous user wrote.
+    fn reset(&mut self, length: usize) {
+        debug_assert!(length <= self.capacity);
+        self.length = length;
+        // SAFETY: length + padding is within the allocation
+        unsafe {
+            self.ptr.as_ptr().add(length).write_bytes(0, self.padding);
+        }
+    }
+
     /// Get raw pointer to the data
     #[inline(always)]
     pub fn as_ptr(&self) -> *const u8 {
         self.ptr.as_ptr()
     }
+
+    /// Get a view of the data that carries the padding guarantee
+    #[inline(always)]
+    pub fn padded(&self) -> Padded<&[u8]> {
+        // SAFETY: the allocation holds at least CSV_PADDING bytes past the
+        // data, which are zeroed on allocation and again on every reuse
+        unsafe { Padded::from_raw_parts(self.ptr.as_ptr(), self.length) }
+    }
+
+    /// Drop the first `n` data bytes, shifting the rest to the front
+    fn consume_prefix(&mut self, n: usize) {
+        let n = n.min(self.length);
+        let length = self.length;
+        self.data_mut().copy_within(n..length, 0);
+        self.reset(length - n);
+    }
 }
 
 impl Drop for PaddedBuffer {
     fn drop(&mut self) {
         unsafe {
-            aligned_free(self.ptr, self.length, self.padding);
+            aligned_free(self.ptr, self.capacity, self.padding);
         }
     }
 }
 
+/// A small pool of `PaddedBuffer`s reused across loads
+///
+/// Services that parse many small inputs can acquire a buffer, parse it and
+/// release it back instead of going through the allocator every time.
+pub struct BufferPool {
+    free: Mutex<Vec<PaddedBuffer>>,
+    max_pooled: usize,
+}
+
+impl BufferPool {
+    /// Create a pool that keeps at most `max_pooled` released buffers
+    pub fn new(max_pooled: usize) -> Self {
+        Self {
+            free: Mutex::new(Vec::with_capacity(max_pooled)),
+            max_pooled,
+        }
+    }
+
+    /// Get a buffer holding `length` data bytes, reusing a pooled one if any
+    /// is large enough
+    ///
+    /// The contents of the data region are unspecified; the padding is zeroed.
+    pub fn acquire(&self, length: usize) -> Result<PaddedBuffer> {
+        let reused = {
+            let mut free = self.free.lock().unwrap_or_else(|e| e.into_inner());
+            free.iter()
+                .position(|buf| buf.capacity >= length)
+                .map(|pos| free.swap_remove(pos))
+        };
+
+        match reused {
+            Some(mut buf) => {
+                buf.reset(length);
+                Ok(buf)
+            }
+            None => PaddedBuffer::new(length, CSV_PADDING),
+        }
+    }
+
+    /// Return a buffer to the pool, dropping it if the pool is full
+    pub fn release(&self, buf: PaddedBuffer) {
+        let mut free = self.free.lock().unwrap_or_else(|e| e.into_inner());
+        if free.len() < self.max_pooled {
+            free.push(buf);
+        }
+    }
+
+    /// Copy `data` into a pooled buffer
+    pub fn copy_from(&self, data: &[u8]) -> Result<PaddedBuffer> {
+        let mut buf = self.acquire(data.len())?;
+        buf.data_mut().copy_from_slice(data);
+        Ok(buf)
+    }
+
+    /// Load a file into a pooled buffer
+    pub fn load(&self, filename: &str) -> Result<PaddedBuffer> {
+        let mut file = open_corpus(filename)?;
+        let length = corpus_len(&file)?;
+        let mut buf = self.acquire(length)?;
+        read_corpus(&mut file, &mut buf)?;
+        Ok(buf)
+    }
+
+    /// Get the number of buffers currently held by the pool
+    pub fn len(&self) -> usize {
+        self.free.lock().unwrap_or_else(|e| e.into_inner()).len()
+    }
+
+    /// Check if the pool holds no buffers
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
 /// Load a file into memory with padding for safe SIMD operations
 ///
 /// # Arguments
 /// * `filename` - Path to the file to load
-/// * `padding` - Number of bytes to pad at the end for safe SIMD reads
+/// * `padding` - Number of bytes to pad at the end for safe SIMD reads,
+///   raised to `CSV_PADDING` if smaller
 ///
 /// # Returns
-/// A `PaddedBuffer` containing the file data with padding
-pub fn get_corpus(filename: &str, padding: usize) -> Result<PaddedBuffer, String> {
-    let mut file =
-        File::open(filename).map_err(|e| format!("Could not open file '{}': {}", filename, e))?;
+/// A `PaddedBuffer` containing the file data followed by zeroed padding
+pub fn get_corpus(filename: &str, padding: usize) -> Result<PaddedBuffer> {
+    let mut file = open_corpus(filename)?;
+    let length = corpus_len(&file)?;
+    let mut buf = PaddedBuffer::new(length, padding)?;
+    read_corpus(&mut file, &mut buf)?;
+    Ok(buf)
+}
 
-    let metadata = file
-        .metadata()
-        .map_err(|e| format!("Could not read file metadata: {}", e))?;
+/// Load a file and convert it to UTF-8 for parsing
+///
+/// The encoding is detected from the byte order mark or the content unless
+/// `encoding` is given. A UTF-8 byte order mark is stripped in place; other
+/// encodings are transcoded into a new buffer.
+///
+/// # Returns
+/// The UTF-8 data and the encoding the file was read as
+pub fn get_corpus_utf8(
+    filename: &str,
+    padding: usize,
+    encoding: Option<Encoding>,
+) -> Result<(PaddedBuffer, DetectedEncoding)> {
+    let mut buf = get_corpus(filename, padding)?;
 
-    let length = metadata.len() as usize;
+    let detected = match encoding {
+        Some(encoding) => DetectedEncoding {
+            encoding,
+            bom: !encoding.bom().is_empty() && buf.data().starts_with(encoding.bom()),
+        },
+        None => detect_encoding(buf.data()),
+    };
 
-    let ptr = allocate_padded_buffer(length, padding)?;
+    if detected.encoding == Encoding::Utf8 {
+        if detected.bom {
+            buf.consume_prefix(Encoding::Utf8.bom().len());
+        }
+        return Ok((buf, detected));
+    }
+
+    let utf8 = transcode_to_utf8(buf.data(), detected.encoding);
+    let mut out = PaddedBuffer::new(utf8.len(), buf.padding)?;
+    out.data_mut().copy_from_slice(&utf8);
+    Ok((out, detected))
+}
+
+fn open_corpus(filename: &str) -> Result<File> {
+    File::open(filename).map_err(|e| Error::io(format!("could not open file '{}'", filename), e))
+}
 
-    // SAFETY: We just allocated this buffer with the correct size
-    let slice = unsafe { std::slice::from_raw_parts_mut(ptr.as_ptr(), length) };
+fn corpus_len(file: &File) -> Result<usize> {
+    let metadata = file
+        .metadata()
+        .map_err(|e| Error::io("could not read file metadata", e))?;
 
-    file.read_exact(slice)
-        .map_err(|e| format!("Could not read file data: {}", e))?;
+    Ok(metadata.len() as usize)
+}
 
-    Ok(PaddedBuffer {
-        ptr,
-        length,
-        padding,
-    })
+fn read_corpus(file: &mut File, buf: &mut PaddedBuffer) -> Result<()> {
+    file.read_exact(buf.data_mut())
+        .map_err(|e| Error::io("could not read file data", e))
 }
 
 #[cfg(test)]
@@ -100,10 +275,23 @@ mod tests {
         assert_eq!(buffer.len(), 18);
         assert_eq!(buffer.data(), b"a,b,c\n1,2,3\n4,5,6\n");
 
+        let padded = buffer.padded();
+        assert_eq!(padded.data(), buffer.data());
+        assert!(padded.with_padding()[18..].iter().all(|&b| b == 0));
+
         // Cleanup
         std::fs::remove_file(test_file).ok();
     }
 
+    #[test]
+    fn test_get_corpus_missing_file() {
+        let err = get_corpus("/nonexistent/simdcsv.csv", 64).err().unwrap();
+        match err {
+            Error::Io { source, .. } => assert_eq!(source.kind(), std::io::ErrorKind::NotFound),
+            other => panic!("unexpected error: {}", other),
+        }
+    }
+
     #[test]
     fn test_padded_buffer_alignment() {
         let temp_dir = std::env::temp_dir();
@@ -125,4 +313,62 @@ mod tests {
 
         std::fs::remove_file(test_file).ok();
     }
+
+    #[test]
+    fn test_get_corpus_utf8() {
+        let temp_dir = std::env::temp_dir();
+        let test_file = temp_dir.join("test_simdcsv_encoding.csv");
+
+        std::fs::write(&test_file, b"\xEF\xBB\xBFid,name\n1,x\n").unwrap();
+        let (buffer, detected) = get_corpus_utf8(test_file.to_str().unwrap(), 64, None).unwrap();
+        assert_eq!(detected.encoding, Encoding::Utf8);
+        assert!(detected.bom);
+        assert_eq!(buffer.data(), b"id,name\n1,x\n");
+        assert!(buffer.padded().with_padding()[buffer.len()..]
+            .iter()
+            .all(|&b| b == 0));
+
+        let utf16: Vec<u8> = "\u{FEFF}id,name\n1,Zoë\n"
+            .encode_utf16()
+            .flat_map(|u| u.to_le_bytes())
+            .collect();
+        std::fs::write(&test_file, utf16).unwrap();
+        let (buffer, detected) = get_corpus_utf8(test_file.to_str().unwrap(), 64, None).unwrap();
+        assert_eq!(detected.encoding, Encoding::Utf16Le);
+        assert_eq!(buffer.data(), "id,name\n1,Zoë\n".as_bytes());
+
+        std::fs::write(&test_file, b"id\n\xE9\n").unwrap();
+        let (buffer, detected) =
+            get_corpus_utf8(test_file.to_str().unwrap(), 64, Some(Encoding::Latin1)).unwrap();
+        assert_eq!(detected.encoding, Encoding::Latin1);
+        assert!(!detected.bom);
+        assert_eq!(buffer.data(), "id\né\n".as_bytes());
+
+        std::fs::remove_file(test_file).ok();
+    }
+
+    #[test]
+    fn test_buffer_pool_reuse() {
+        let pool = BufferPool::new(2);
+
+        let buf = pool.copy_from(b"a,b,c\n1,2,3\n").unwrap();
+        let ptr = buf.as_ptr();
+        pool.release(buf);
+        assert_eq!(pool.len(), 1);
+
+        // A smaller request reuses the same allocation with fresh padding
+        let buf = pool.copy_from(b"x\n").unwrap();
+        assert_eq!(buf.as_ptr(), ptr);
+        assert_eq!(buf.data(), b"x\n");
+        assert!(buf.padded().with_padding()[2..].iter().all(|&b| b == 0));
+        assert!(pool.is_empty());
+
+        // A larger request allocates a new buffer
+        let big = pool.acquire(1024).unwrap();
+        assert_eq!(big.len(), 1024);
+        pool.release(big);
+        pool.release(buf);
+        pool.release(PaddedBuffer::new(8, 64).unwrap());
+        assert_eq!(pool.len(), 2);
+    }
 }
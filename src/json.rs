@@ -0,0 +1,241 @@
+//! Conversion of parsed records to JSON
+//!
+//! Each record becomes an object keyed by the header names. With type
+//! inference, numbers and booleans are written bare and nulls as `null`.
+
+use crate::conventions::{Classifier, ValueConventions};
+use crate::error::{Error, Result};
+use crate::float::parse_f64;
+use crate::header::HeaderNormalization;
+use crate::numeric::{digits_kernel, parse_i64_with, DigitsKernel};
+use crate::parser::ParsedCsv;
+use crate::record::Record;
+use crate::schema::{infer_schema_with, write_json_string, DataType};
+use std::fmt::Write as _;
+use std::io::Write;
+
+/// Layout of the JSON output
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum JsonFormat {
+    /// One JSON array holding every record
+    #[default]
+    Array,
+    /// One object per line (NDJSON)
+    Lines,
+}
+
+/// Options for `write_json`
+#[derive(Clone, Debug, Default)]
+pub struct JsonOptions {
+    pub format: JsonFormat,
+    /// Write numbers, booleans and nulls unquoted, as inferred by
+    /// `infer_schema_with`; every field is a string otherwise
+    pub infer_types: bool,
+    /// Infer types from the first `sample` records, or from all with `None`
+    pub sample: Option<usize>,
+    /// Null and boolean tokens for type inference
+    pub conventions: ValueConventions,
+}
+
+/// Write the records of `pcsv` as JSON objects to `out`
+///
+/// Keys are the header names, made unique, or `column_1`, `column_2`, ...
+/// without a header. Fields missing from short records are `null`. A field
+/// that does not fit its inferred type is written as a string. Blank lines
+/// are skipped.
+pub fn write_json<W: Write>(
+    pcsv: &ParsedCsv,
+    buf: &[u8],
+    options: &JsonOptions,
+    mut out: W,
+) -> Result<()> {
+    let normalization = HeaderNormalization {
+        dedupe: true,
+        ..HeaderNormalization::default()
+    };
+    let names = pcsv.header_names(buf, &normalization).unwrap_or_default();
+    let types: Vec<DataType> = if options.infer_types {
+        infer_schema_with(pcsv, buf, options.sample, &options.conventions)
+            .fields
+            .iter()
+            .map(|field| field.data_type)
+            .collect()
+    } else {
+        Vec::new()
+    };
+    let classifier = Classifier::new(&options.conventions);
+    let kernel = digits_kernel();
+
+    let mut line = String::new();
+    let mut first = true;
+    let (open, separator, close) = match options.format {
+        JsonFormat::Array => ("[", ",\n", "]\n"),
+        JsonFormat::Lines => ("", "\n", "\n"),
+    };
+    let write = |out: &mut W, s: &str| {
+        out.write_all(s.as_bytes())
+            .map_err(|e| Error::io("failed to write JSON output", e))
+    };
+
+    write(&mut out, open)?;
+    for record in pcsv.records(buf) {
+        if record.range().is_empty() {
+            continue;
+        }
+        line.clear();
+        if !first {
+            line.push_str(separator);
+        }
+        first = false;
+        write_object(&mut line, &record, &names, &types, &classifier, kernel);
+        write(&mut out, &line)?;
+    }
+    // An empty NDJSON output has no lines at all
+    if !first || options.format == JsonFormat::Array {
+        write(&mut out, close)?;
+    }
+    out.flush()
+        .map_err(|e| Error::io("failed to flush JSON output", e))
+}
+
+/// Convert the records of `pcsv` to a JSON string
+pub fn to_json(pcsv: &ParsedCsv, buf: &[u8], options: &JsonOptions) -> String {
+    let mut out = Vec::new();
+    // Writing to a Vec cannot fail
+    let _ = write_json(pcsv, buf, options, &mut out);
+    String::from_utf8(out).expect("JSON output is UTF-8")
+}
+
+fn write_object(
+    out: &mut String,
+    record: &Record<'_>,
+    names: &[String],
+    types: &[DataType],
+    classifier: &Classifier,
+    kernel: DigitsKernel,
+) {
+    out.push('{');
+    for i in 0..names.len().max(record.len()) {
+        if i > 0 {
+            out.push(',');
+        }
+        match names.get(i) {
+            Some(name) => write_json_string(out, name),
+            None => {
+                let _ = write!(out, "\"column_{}\"", i + 1);
+            }
+        }
+        out.push(':');
+        match record.get_unquoted(i) {
+            Some(field) => write_value(out, &field, types.get(i).copied(), classifier, kernel),
+            None => out.push_str("null"),
+        }
+    }
+    out.push('}');
+}
+
+/// Append `field` as a JSON value of type `data_type`, or as a string
+fn write_value(
+    out: &mut String,
+    field: &[u8],
+    data_type: Option<DataType>,
+    classifier: &Classifier,
+    kernel: DigitsKernel,
+) {
+    if let Some(data_type) = data_type {
+        if classifier.is_null(field) {
+            out.push_str("null");
+            return;
+        }
+        match data_type {
+            DataType::Boolean => {
+                if let Ok(value) = classifier.parse_bool(field) {
+                    let _ = write!(out, "{}", value);
+                    return;
+                }
+            }
+            DataType::Int64 => {
+                if let Ok(value) = parse_i64_with(field, kernel) {
+                    let _ = write!(out, "{}", value);
+                    return;
+                }
+            }
+            DataType::Float64 => {
+                // JSON has no infinities or NaN
+                if let Some(value) = parse_f64(field).ok().filter(|v| v.is_finite()) {
+                    let _ = write!(out, "{:?}", value);
+                    return;
+                }
+            }
+            _ => {}
+        }
+    }
+    write_json_string(out, &String::from_utf8_lossy(field));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::memory::Padded;
+    use crate::parser::{parse_csv_with, ParseOptions};
+
+    fn parse(data: &[u8], has_header: bool) -> ParsedCsv {
+        let options = ParseOptions {
+            has_header,
+            ..ParseOptions::default()
+        };
+        parse_csv_with(Padded::from_slice(data).as_padded(), &options).unwrap()
+    }
+
+    #[test]
+    fn test_strings() {
+        let data = b"id,name,id\n1,\"say \"\"hi\"\"\\\ttab\",x\n2\n";
+        let pcsv = parse(data, true);
+        let json = to_json(&pcsv, data, &JsonOptions::default());
+        assert_eq!(
+            json,
+            "[{\"id\":\"1\",\"name\":\"say \\\"hi\\\"\\\\\\ttab\",\"id_2\":\"x\"},\n\
+             {\"id\":\"2\",\"name\":null,\"id_2\":null}]\n"
+        );
+
+        let lines = JsonOptions {
+            format: JsonFormat::Lines,
+            ..JsonOptions::default()
+        };
+        let data = b"a\n\"x\ny\"\n\r\n\"\"\n\n";
+        let pcsv = parse(data, false);
+        assert_eq!(
+            to_json(&pcsv, data, &lines),
+            "{\"column_1\":\"a\"}\n{\"column_1\":\"x\\ny\"}\n{\"column_1\":\"\"}\n"
+        );
+        let empty = parse(b"", false);
+        assert_eq!(to_json(&empty, b"", &lines), "");
+        assert_eq!(to_json(&empty, b"", &JsonOptions::default()), "[]\n");
+    }
+
+    #[test]
+    fn test_infer_types() {
+        let data =
+            b"b,i,f,d,s\ntrue,+7,1e300,2012-08-16,1\nFALSE,-2,-0.5,,NA\n,x,inf,2012-08-17,\n";
+        let pcsv = parse(data, true);
+        let options = JsonOptions {
+            format: JsonFormat::Lines,
+            infer_types: true,
+            sample: Some(2),
+            conventions: ValueConventions {
+                nulls: vec!["NA".to_string()],
+                ..ValueConventions::default()
+            },
+        };
+        let json = to_json(&pcsv, data, &options);
+        let lines: Vec<&str> = json.lines().collect();
+        assert_eq!(
+            lines,
+            [
+                "{\"b\":true,\"i\":7,\"f\":1e300,\"d\":\"2012-08-16\",\"s\":1}",
+                "{\"b\":false,\"i\":-2,\"f\":-0.5,\"d\":null,\"s\":null}",
+                "{\"b\":null,\"i\":\"x\",\"f\":\"inf\",\"d\":\"2012-08-17\",\"s\":null}",
+            ]
+        );
+    }
+}
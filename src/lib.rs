@@ -5,12 +5,69 @@
 //! This library leverages SIMD intrinsics (AVX2 on x86_64, NEON on ARM)
 //! and LLVM's vectorization capabilities for high-performance CSV parsing.
 
+#[cfg(feature = "arrow")]
+pub mod arrow;
+#[cfg(feature = "async")]
+pub mod async_reader;
+pub mod column;
+pub mod column_index;
+pub mod conventions;
+pub mod datetime;
+#[cfg(feature = "serde")]
+pub mod de;
+pub mod decimal;
+pub mod encoding;
+pub mod error;
+pub mod float;
+mod float_table;
+pub mod header;
 pub mod io;
+pub mod json;
+pub mod limits;
+pub mod line_index;
 pub mod memory;
+pub mod numeric;
 pub mod parser;
 pub mod portability;
+pub mod projection;
+pub mod ragged;
+pub mod record;
+pub mod recovery;
+pub mod schema;
+pub mod strict;
+pub mod utf8;
+pub mod writer;
 
-pub use parser::{parse_csv, ParsedCsv};
+#[cfg(feature = "arrow")]
+pub use arrow::{to_record_batches, ArrowOptions};
+#[cfg(feature = "async")]
+pub use async_reader::{AsyncCsvReader, CsvBatch};
+pub use column::{Bitmap, Column, FieldError, TypedColumn};
+pub use column_index::ColumnIndex;
+pub use conventions::{InvalidBool, ValueConventions};
+pub use datetime::{DatePattern, DateTimeError};
+#[cfg(feature = "serde")]
+pub use de::DeserializeRecords;
+pub use decimal::{DecimalError, DecimalFormat};
+pub use encoding::{DetectedEncoding, Encoding};
+pub use error::{Error, Limit, ParseErrorKind, Result};
+pub use header::{HeaderIssue, HeaderMap, HeaderNormalization};
+pub use json::{to_json, write_json, JsonFormat, JsonOptions};
+pub use limits::Limits;
+pub use line_index::{LineIndex, Position};
+pub use memory::Padded;
+pub use numeric::NumberError;
+pub use parser::{
+    parse_csv, parse_csv_utf8, parse_csv_with, parse_into, parse_into_with, ParseOptions,
+    ParsedCsv, ParserState,
+};
+pub use projection::{project_csv, ColumnSelection, ProjectedCsv};
+pub use ragged::{RaggedMode, RaggedRow};
+pub use record::{unquote, Record, Records};
+pub use recovery::{RecordError, RecoveryOptions};
+pub use schema::{infer_schema, infer_schema_with, DataType, Schema, SchemaField};
+pub use strict::{Violation, ViolationKind};
+pub use writer::{CsvWriter, Dialect, QuotePolicy, Terminator};
 
 /// CSV padding size for safe SIMD reads
 pub const CSV_PADDING: usize = 64;
@@ -0,0 +1,281 @@
+//! Resource limits for untrusted input
+//!
+//! The checker runs inside the scan loop on the masks of each 64-byte
+//! block, so an input that breaks a limit is rejected before the rest of it
+//! is indexed.
+
+use crate::error::{Error, Limit, Result};
+use crate::portability::trailing_zeros;
+
+/// Upper bounds on the shape of the input; `None` leaves a bound off
+#[derive(Clone, Debug, Default)]
+pub struct Limits {
+    /// Longest field in bytes, quotes included
+    pub max_field_len: Option<usize>,
+    /// Most fields in one record
+    pub max_fields_per_record: Option<usize>,
+    /// Most records in the input
+    pub max_records: Option<usize>,
+    /// Most memory used by `ParsedCsv::indexes`, in bytes
+    pub max_index_bytes: Option<usize>,
+}
+
+impl Limits {
+    /// Check if no limit is set
+    pub fn is_unlimited(&self) -> bool {
+        self.max_field_len.is_none()
+            && self.max_fields_per_record.is_none()
+            && self.max_records.is_none()
+            && self.max_index_bytes.is_none()
+    }
+}
+
+/// Block-at-a-time limit enforcement
+///
+/// Offsets are relative to the buffer being scanned; the counts carry over
+/// from one scan to the next.
+#[derive(Clone, Copy, Debug)]
+pub(crate) struct LimitChecker {
+    max_field_len: usize,
+    max_fields: usize,
+    max_records: usize,
+    max_indexes: usize,
+    max_index_bytes: usize,
+    /// Whether any limit is set, so the scan needs the checker at all
+    active: bool,
+    field_start: usize,
+    record_start: usize,
+    fields: usize,
+    records: usize,
+    breach: Option<(Limit, usize)>,
+}
+
+impl Default for LimitChecker {
+    fn default() -> Self {
+        Self::new(&Limits::default())
+    }
+}
+
+impl LimitChecker {
+    pub(crate) fn new(limits: &Limits) -> Self {
+        Self {
+            max_field_len: limits.max_field_len.unwrap_or(usize::MAX),
+            max_fields: limits.max_fields_per_record.unwrap_or(usize::MAX),
+            max_records: limits.max_records.unwrap_or(usize::MAX),
+            max_indexes: max_indexes(limits),
+            max_index_bytes: limits.max_index_bytes.unwrap_or(usize::MAX),
+            active: !limits.is_unlimited(),
+            field_start: 0,
+            record_start: 0,
+            fields: 1,
+            records: 0,
+            breach: None,
+        }
+    }
+
+    /// Check if any limit is set
+    #[inline(always)]
+    pub(crate) fn is_active(&self) -> bool {
+        self.active
+    }
+
+    fn max(&self, limit: Limit) -> usize {
+        match limit {
+            Limit::FieldLength => self.max_field_len,
+            Limit::FieldsPerRecord => self.max_fields,
+            Limit::Records => self.max_records,
+            Limit::IndexMemory => self.max_index_bytes,
+            _ => usize::MAX,
+        }
+    }
+
+    fn breach(&mut self, limit: Limit, offset: usize) -> bool {
+        self.breach = Some((limit, offset));
+        false
+    }
+
+    /// Take the breach found by `check_block`, if any
+    pub(crate) fn take_error(&mut self) -> Option<Error> {
+        let (limit, offset) = self.breach.take()?;
+        Some(Error::LimitExceeded {
+            limit,
+            max: self.max(limit),
+            offset,
+        })
+    }
+
+    /// Continue at `offset` after `records` complete records, the last of
+    /// which started at `record_start`
+    ///
+    /// Used when a rescan replaces the records checked after `offset`.
+    pub(crate) fn resume(
+        &mut self,
+        records: usize,
+        record_start: usize,
+        offset: usize,
+    ) -> Result<()> {
+        self.records = records;
+        self.fields = 1;
+        self.field_start = offset;
+        self.record_start = offset;
+        if records > self.max_records {
+            return Err(Error::LimitExceeded {
+                limit: Limit::Records,
+                max: self.max_records,
+                offset: record_start,
+            });
+        }
+        Ok(())
+    }
+
+    /// Move the stored offsets back by `by` bytes, after the caller dropped
+    /// that much input from the front of its buffer
+    #[cfg(feature = "async")]
+    pub(crate) fn rebase(&mut self, by: usize) {
+        self.field_start = self.field_start.saturating_sub(by);
+        self.record_start = self.record_start.saturating_sub(by);
+    }
+
+    /// Check the separators of the block at `idx` before they are indexed
+    ///
+    /// `seps` holds every unquoted separator and `ends` the record
+    /// terminators among them. `indexes` is the index count including this
+    /// block. Returns false once a limit is breached.
+    #[inline(always)]
+    pub(crate) fn check_block(
+        &mut self,
+        idx: usize,
+        len: usize,
+        mut seps: u64,
+        ends: u64,
+        indexes: usize,
+    ) -> bool {
+        if indexes > self.max_indexes {
+            return self.breach(Limit::IndexMemory, idx);
+        }
+
+        while seps != 0 {
+            let bit = trailing_zeros(seps);
+            let pos = idx + bit as usize;
+            if pos - self.field_start > self.max_field_len {
+                return self.breach(Limit::FieldLength, self.field_start);
+            }
+            if ends & (1 << bit) != 0 {
+                self.records += 1;
+                if self.records > self.max_records {
+                    return self.breach(Limit::Records, self.record_start);
+                }
+                self.fields = 1;
+                self.record_start = pos + 1;
+            } else {
+                self.fields += 1;
+                if self.fields > self.max_fields {
+                    return self.breach(Limit::FieldsPerRecord, pos);
+                }
+            }
+            self.field_start = pos + 1;
+            seps &= seps - 1;
+        }
+
+        // A field may run on without separators for many blocks
+        let block_end = (idx + 64).min(len);
+        if block_end - self.field_start > self.max_field_len {
+            return self.breach(Limit::FieldLength, self.field_start);
+        }
+        true
+    }
+
+    /// Count a final unterminated record and return the first breach
+    pub(crate) fn finish(&mut self, len: usize) -> Option<Error> {
+        if self.breach.is_none() && self.record_start < len && self.records + 1 > self.max_records {
+            self.breach(Limit::Records, self.record_start);
+        }
+        self.take_error()
+    }
+}
+
+/// Most indexes `limits` lets `ParsedCsv::indexes` hold
+pub(crate) fn max_indexes(limits: &Limits) -> usize {
+    limits
+        .max_index_bytes
+        .map_or(usize::MAX, |bytes| bytes / std::mem::size_of::<u32>())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::memory::Padded;
+    use crate::parser::{parse_csv_with, ParseOptions};
+
+    fn parse(data: &[u8], limits: Limits) -> Option<Error> {
+        let options = ParseOptions {
+            limits,
+            ..ParseOptions::default()
+        };
+        parse_csv_with(Padded::from_slice(data).as_padded(), &options).err()
+    }
+
+    fn breach(err: Option<Error>) -> (Limit, usize, usize) {
+        match err {
+            Some(Error::LimitExceeded { limit, max, offset }) => (limit, max, offset),
+            other => panic!("expected a limit error, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_field_length() {
+        let mut data = b"a,b\n1,\"".to_vec();
+        data.extend_from_slice(&[b'x'; 500]);
+        data.extend_from_slice(b"\"\n");
+        let limits = Limits {
+            max_field_len: Some(100),
+            ..Limits::default()
+        };
+        assert_eq!(
+            breach(parse(&data, limits.clone())),
+            (Limit::FieldLength, 100, 6)
+        );
+        assert!(parse(b"a,b\n1,2\n", limits).is_none());
+    }
+
+    #[test]
+    fn test_fields_and_records() {
+        let data = b"a,b,c\n1,2,3\n4,5,6,7\n8,9,10\n";
+        let limits = Limits {
+            max_fields_per_record: Some(3),
+            ..Limits::default()
+        };
+        assert_eq!(breach(parse(data, limits)), (Limit::FieldsPerRecord, 3, 17));
+
+        let limits = Limits {
+            max_records: Some(3),
+            ..Limits::default()
+        };
+        assert_eq!(breach(parse(data, limits.clone())), (Limit::Records, 3, 20));
+        assert!(parse(b"a\nb\nc\n", limits.clone()).is_none());
+        assert_eq!(breach(parse(b"a\nb\nc\nd", limits)), (Limit::Records, 3, 6));
+    }
+
+    #[test]
+    fn test_index_memory() {
+        let data = b"a,".repeat(1000);
+        let limits = Limits {
+            max_index_bytes: Some(1024),
+            ..Limits::default()
+        };
+        let (limit, max, offset) = breach(parse(&data, limits.clone()));
+        assert_eq!((limit, max), (Limit::IndexMemory, 1024));
+        assert!(offset <= 512);
+
+        // Neither the initial estimate nor growth reserves past the limit
+        let mut data = vec![b'x'; 100_000];
+        data.extend(b",".repeat(200));
+        let options = ParseOptions {
+            limits,
+            ..ParseOptions::default()
+        };
+        let pcsv = parse_csv_with(Padded::from_slice(&data).as_padded(), &options).unwrap();
+        assert_eq!(pcsv.indexes.len(), 200);
+        assert!(pcsv.indexes.capacity() * 4 <= 1024);
+    }
+}
@@ -0,0 +1,233 @@
+//! Mapping between byte offsets, lines and records
+//!
+//! Offsets reported by the parser are raw byte positions. `LineIndex` keeps
+//! the position of every line feed so they can be shown as line and column
+//! in an editor. Physical lines end at every `\n`; logical lines (records)
+//! end only at a `\n` outside quotes, so a record with a quoted line break
+//! spans several physical lines.
+
+use crate::error::Result;
+use crate::memory::Padded;
+use crate::parser::check_input_len;
+#[cfg(any(target_arch = "x86_64", target_arch = "aarch64"))]
+use crate::parser::{cmp_mask_against_input, fill_input, find_quote_mask};
+use crate::portability::trailing_zeros;
+use std::ops::Range;
+
+/// A 1-based line and byte column
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Position {
+    pub line: usize,
+    pub column: usize,
+}
+
+/// Line feed positions of an input, split into physical and logical lines
+#[derive(Clone, Debug, Default)]
+pub struct LineIndex {
+    /// Every line feed
+    lines: Vec<u32>,
+    /// Line feeds outside quoted fields
+    records: Vec<u32>,
+    len: usize,
+}
+
+impl LineIndex {
+    /// Build the index of `buf` with the SIMD line feed and quote scan
+    pub fn new(buf: Padded<&[u8]>) -> Result<Self> {
+        check_input_len(buf.len())?;
+        let mut index = LineIndex {
+            len: buf.len(),
+            ..LineIndex::default()
+        };
+        index.scan(buf);
+        Ok(index)
+    }
+
+    #[cfg(target_arch = "x86_64")]
+    fn scan(&mut self, buf: Padded<&[u8]>) {
+        if is_x86_feature_detected!("avx2") && is_x86_feature_detected!("pclmulqdq") {
+            unsafe { self.scan_avx2(buf) }
+        } else {
+            self.scan_scalar(buf.data())
+        }
+    }
+
+    #[cfg(target_arch = "aarch64")]
+    fn scan(&mut self, buf: Padded<&[u8]>) {
+        unsafe { self.scan_blocks(buf) }
+    }
+
+    #[cfg(not(any(target_arch = "x86_64", target_arch = "aarch64")))]
+    fn scan(&mut self, buf: Padded<&[u8]>) {
+        self.scan_scalar(buf.data())
+    }
+
+    #[cfg(target_arch = "x86_64")]
+    #[target_feature(enable = "avx2")]
+    #[target_feature(enable = "pclmulqdq")]
+    unsafe fn scan_avx2(&mut self, buf: Padded<&[u8]>) {
+        self.scan_blocks(buf)
+    }
+
+    /// Scan 64-byte blocks; the last one reads into the zeroed padding
+    #[cfg(any(target_arch = "x86_64", target_arch = "aarch64"))]
+    #[inline(always)]
+    unsafe fn scan_blocks(&mut self, buf: Padded<&[u8]>) {
+        let ptr = buf.with_padding().as_ptr();
+        let mut prev_iter_inside_quote = 0u64;
+        let mut idx = 0;
+
+        while idx < self.len {
+            let input = fill_input(ptr.add(idx));
+            let quote_mask = find_quote_mask(input, &mut prev_iter_inside_quote);
+            let newlines = cmp_mask_against_input(input, b'\n');
+
+            push_bits(&mut self.lines, idx, newlines);
+            push_bits(&mut self.records, idx, newlines & !quote_mask);
+            idx += 64;
+        }
+    }
+
+    #[cfg_attr(target_arch = "aarch64", allow(dead_code))]
+    fn scan_scalar(&mut self, data: &[u8]) {
+        let mut in_quote = false;
+        for (i, &byte) in data.iter().enumerate() {
+            match byte {
+                b'"' => in_quote = !in_quote,
+                b'\n' => {
+                    self.lines.push(i as u32);
+                    if !in_quote {
+                        self.records.push(i as u32);
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+
+    /// Get the number of physical lines
+    pub fn line_count(&self) -> usize {
+        count_lines(&self.lines, self.len)
+    }
+
+    /// Get the number of records (logical lines)
+    pub fn record_count(&self) -> usize {
+        count_lines(&self.records, self.len)
+    }
+
+    /// Map a byte offset to its physical line and column
+    ///
+    /// An offset at a line feed belongs to the line it ends. Offsets past the
+    /// end of the input are clamped to it.
+    pub fn position(&self, offset: usize) -> Position {
+        let offset = offset.min(self.len);
+        let line = self.lines.partition_point(|&nl| (nl as usize) < offset);
+        let line_start = match line {
+            0 => 0,
+            n => self.lines[n - 1] as usize + 1,
+        };
+        Position {
+            line: line + 1,
+            column: offset - line_start + 1,
+        }
+    }
+
+    /// Get the 1-based record number holding a byte offset
+    pub fn record_of(&self, offset: usize) -> usize {
+        let offset = offset.min(self.len);
+        self.records.partition_point(|&nl| (nl as usize) < offset) + 1
+    }
+
+    /// Get the byte range of 1-based `record`, excluding its terminator
+    pub fn record_range(&self, record: usize) -> Option<Range<usize>> {
+        line_range(&self.records, self.len, record)
+    }
+
+    /// Get the byte range of 1-based physical `line`, excluding its terminator
+    pub fn line_range(&self, line: usize) -> Option<Range<usize>> {
+        line_range(&self.lines, self.len, line)
+    }
+}
+
+#[cfg(any(target_arch = "x86_64", target_arch = "aarch64"))]
+#[inline(always)]
+fn push_bits(out: &mut Vec<u32>, idx: usize, mut bits: u64) {
+    while bits != 0 {
+        out.push((idx + trailing_zeros(bits) as usize) as u32);
+        bits &= bits - 1;
+    }
+}
+
+/// Lines ended by `ends`, plus a final line without a terminator
+fn count_lines(ends: &[u32], len: usize) -> usize {
+    match ends.last() {
+        Some(&last) if last as usize + 1 == len => ends.len(),
+        _ if len == 0 => 0,
+        _ => ends.len() + 1,
+    }
+}
+
+fn line_range(ends: &[u32], len: usize, line: usize) -> Option<Range<usize>> {
+    if line == 0 || line > count_lines(ends, len) {
+        return None;
+    }
+    let start = match line {
+        1 => 0,
+        n => ends[n - 2] as usize + 1,
+    };
+    let end = ends.get(line - 1).map_or(len, |&nl| nl as usize);
+    Some(start..end)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DATA: &[u8] = b"id,note\n1,\"two\nlines\"\n2,x\n3,last";
+
+    #[test]
+    fn test_positions() {
+        let padded = Padded::from_slice(DATA);
+        let index = LineIndex::new(padded.as_padded()).unwrap();
+
+        assert_eq!(index.line_count(), 5);
+        assert_eq!(index.record_count(), 4);
+        assert_eq!(index.position(0), Position { line: 1, column: 1 });
+        assert_eq!(index.position(7), Position { line: 1, column: 8 });
+        assert_eq!(index.position(16), Position { line: 3, column: 2 });
+        assert_eq!(index.record_of(16), 2);
+        assert_eq!(index.record_of(DATA.len()), 4);
+
+        assert_eq!(index.record_range(2), Some(8..21));
+        assert_eq!(index.record_range(4), Some(26..32));
+        assert_eq!(index.record_range(5), None);
+        assert_eq!(index.line_range(3), Some(15..21));
+    }
+
+    #[test]
+    fn test_matches_scalar() {
+        let data = DATA.repeat(13);
+        let padded = Padded::from_slice(&data);
+        let index = LineIndex::new(padded.as_padded()).unwrap();
+        let mut scalar = LineIndex {
+            len: data.len(),
+            ..LineIndex::default()
+        };
+        scalar.scan_scalar(&data);
+        assert_eq!(index.lines, scalar.lines);
+        assert_eq!(index.records, scalar.records);
+    }
+
+    #[test]
+    fn test_empty_and_trailing_newline() {
+        let padded = Padded::from_slice(b"");
+        let index = LineIndex::new(padded.as_padded()).unwrap();
+        assert_eq!(index.line_count(), 0);
+        assert_eq!(index.position(5), Position { line: 1, column: 1 });
+
+        let padded = Padded::from_slice(b"a\nb\n");
+        let index = LineIndex::new(padded.as_padded()).unwrap();
+        assert_eq!(index.line_count(), 2);
+        assert_eq!(index.line_range(2), Some(2..3));
+    }
+}
@@ -2,17 +2,34 @@
 //!
 //! A high-performance CSV parser leveraging SIMD intrinsics and LLVM vectorization.
 
-use clap::Parser;
-use simdcsv::{io::get_corpus, parser::parse_csv, CSV_PADDING};
+use clap::{Parser, Subcommand};
+use simdcsv::{
+    header::header_issues,
+    infer_schema,
+    io::{get_corpus_utf8, PaddedBuffer},
+    parser::{parse_csv_with, parse_into_with, ParseOptions, ParsedCsv},
+    project_csv, write_json, ColumnSelection, Error, HeaderNormalization, JsonFormat, JsonOptions,
+    LineIndex, CSV_PADDING,
+};
 use std::time::Instant;
 
+/// Violations printed before the strict-mode summary
+const MAX_REPORTED_VIOLATIONS: usize = 20;
+
 #[derive(Parser, Debug)]
 #[command(name = "simdcsv")]
 #[command(about = "A fast SIMD parser for CSV files", long_about = None)]
+#[command(args_conflicts_with_subcommands = true, subcommand_negates_reqs = true)]
 struct Args {
-    /// CSV file to parse
-    #[arg(value_name = "FILE")]
-    file: String,
+    #[command(subcommand)]
+    command: Option<Command>,
+
+    /// CSV file to benchmark
+    #[arg(value_name = "FILE", required = true)]
+    file: Option<String>,
+
+    #[command(flatten)]
+    parse: ParseArgs,
 
     /// Verbose output
     #[arg(short, long)]
@@ -22,35 +39,214 @@ struct Args {
     #[arg(short, long)]
     dump: bool,
 
+    /// Benchmark a projection of these columns (positions or header names,
+    /// comma separated) instead of the full parse
+    #[arg(short, long, value_name = "COLUMNS")]
+    columns: Option<String>,
+
     /// Number of iterations for benchmarking
     #[arg(short, long, default_value = "100")]
     iterations: usize,
 }
 
+/// Parse options shared by the benchmark and the subcommands
+#[derive(clap::Args, Debug)]
+struct ParseArgs {
+    /// Validate UTF-8 during parsing
+    #[arg(short, long)]
+    utf8: bool,
+
+    /// Reject bytes outside quoted fields that RFC 4180 does not allow
+    #[arg(short, long)]
+    strict: bool,
+
+    /// Treat the first record as a header
+    #[arg(short = 'H', long)]
+    header: bool,
+}
+
+impl ParseArgs {
+    fn options(&self) -> ParseOptions {
+        ParseOptions {
+            validate_utf8: self.utf8,
+            strict: self.strict,
+            has_header: self.header,
+            ..ParseOptions::default()
+        }
+    }
+}
+
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Print the records as a JSON array of objects
+    Json {
+        /// CSV file to convert
+        #[arg(value_name = "FILE")]
+        file: String,
+
+        #[command(flatten)]
+        parse: ParseArgs,
+
+        /// Print one object per line (NDJSON) instead of an array
+        #[arg(long)]
+        ndjson: bool,
+
+        /// Write numbers, booleans and nulls unquoted
+        #[arg(long)]
+        typed: bool,
+
+        /// Infer the types from the first N records instead of all of them
+        #[arg(long, value_name = "N")]
+        sample: Option<usize>,
+    },
+    /// Print the inferred column types as JSON
+    Schema {
+        /// CSV file to inspect
+        #[arg(value_name = "FILE")]
+        file: String,
+
+        #[command(flatten)]
+        parse: ParseArgs,
+
+        /// Infer the types from the first N records instead of all of them
+        #[arg(long, value_name = "N")]
+        sample: Option<usize>,
+    },
+}
+
 fn main() {
     let args = Args::parse();
 
-    if args.verbose {
-        println!("[verbose] loading {}", args.file);
+    match &args.command {
+        Some(Command::Json {
+            file,
+            parse,
+            ndjson,
+            typed,
+            sample,
+        }) => {
+            let buffer = load(file, false);
+            let pcsv = parse_or_exit(file, &buffer, &parse.options());
+            let json = JsonOptions {
+                format: if *ndjson {
+                    JsonFormat::Lines
+                } else {
+                    JsonFormat::Array
+                },
+                infer_types: *typed,
+                sample: *sample,
+                ..JsonOptions::default()
+            };
+            let out = std::io::BufWriter::new(std::io::stdout().lock());
+            if let Err(e) = write_json(&pcsv, buffer.data(), &json, out) {
+                eprintln!("Could not convert the file {}: {}", file, e);
+                std::process::exit(1);
+            }
+        }
+        Some(Command::Schema {
+            file,
+            parse,
+            sample,
+        }) => {
+            let buffer = load(file, false);
+            let pcsv = parse_or_exit(file, &buffer, &parse.options());
+            let schema = infer_schema(&pcsv, buffer.data(), *sample);
+            println!("{}", schema.to_json());
+        }
+        None => benchmark(&args),
     }
+}
 
-    // Load file into memory with padding
-    let buffer = match get_corpus(&args.file, CSV_PADDING) {
-        Ok(buf) => buf,
+/// Load a file into a padded buffer, transcoding it to UTF-8
+fn load(file: &str, verbose: bool) -> PaddedBuffer {
+    if verbose {
+        println!("[verbose] loading {}", file);
+    }
+
+    let (buffer, detected) = match get_corpus_utf8(file, CSV_PADDING, None) {
+        Ok(loaded) => loaded,
         Err(e) => {
-            eprintln!("Could not load the file {}: {}", args.file, e);
+            eprintln!("Could not load the file {}: {}", file, e);
             std::process::exit(1);
         }
     };
 
-    if args.verbose {
-        println!("[verbose] loaded {} ({} bytes)", args.file, buffer.len());
+    if verbose {
+        println!("[verbose] loaded {} ({} bytes)", file, buffer.len());
+        println!(
+            "[verbose] encoding {:?}{}",
+            detected.encoding,
+            if detected.bom { " with BOM" } else { "" }
+        );
+    }
+    buffer
+}
+
+/// Parse the buffer, exiting with the error or the strict-mode violations
+/// reported as line and column, as editors show them
+fn parse_or_exit(file: &str, buffer: &PaddedBuffer, options: &ParseOptions) -> ParsedCsv {
+    let pcsv = match parse_csv_with(buffer.padded(), options) {
+        Ok(pcsv) => pcsv,
+        Err(e) => {
+            if let Error::Parse { offset, .. } = e {
+                if let Ok(lines) = LineIndex::new(buffer.padded()) {
+                    let pos = lines.position(offset);
+                    eprintln!("{}:{}:{}: {}", file, pos.line, pos.column, e);
+                    std::process::exit(1);
+                }
+            }
+            eprintln!("Could not parse the file {}: {}", file, e);
+            std::process::exit(1);
+        }
+    };
+
+    if pcsv.violations.is_empty() {
+        return pcsv;
+    }
+    if let Ok(lines) = LineIndex::new(buffer.padded()) {
+        for v in pcsv.violations.iter().take(MAX_REPORTED_VIOLATIONS) {
+            let pos = lines.position(v.offset);
+            eprintln!(
+                "{}:{}:{}: {:?} at byte {}",
+                file, pos.line, pos.column, v.kind, v.offset
+            );
+        }
     }
+    eprintln!(
+        "{}: {} bytes violate RFC 4180 TEXTDATA",
+        file,
+        pcsv.violations.len()
+    );
+    std::process::exit(1);
+}
+
+/// Time repeated parses of the file and print the throughput
+fn benchmark(args: &Args) {
+    let file = args
+        .file
+        .as_deref()
+        .expect("FILE is required without a subcommand");
+    let buffer = load(file, args.verbose);
 
     // Warmup run
-    let pcsv = parse_csv(buffer.data());
+    let options = args.parse.options();
+    let pcsv = parse_or_exit(file, &buffer, &options);
+
+    // Byte offsets are dumped as line and column, as editors show them
+    let lines = if args.dump {
+        LineIndex::new(buffer.padded()).ok()
+    } else {
+        None
+    };
 
     if args.verbose {
+        let names = pcsv.header_names(buffer.data(), &HeaderNormalization::default());
+        if let Some(names) = names {
+            println!("[verbose] header: {}", names.join(", "));
+            for issue in header_issues(&names) {
+                println!("[verbose] header issue: {:?}", issue);
+            }
+        }
         println!("number of indexes found    : {}", pcsv.indexes.len());
         if !pcsv.indexes.is_empty() {
             println!(
@@ -60,26 +256,53 @@ fn main() {
         }
     }
 
-    // Benchmark runs
+    let selection = args.columns.as_deref().map(parse_selection);
+    if let Some(selection) = &selection {
+        match project_csv(buffer.padded(), selection, args.parse.header) {
+            Ok(projected) if args.verbose => {
+                println!("number of projected records: {}", projected.len());
+            }
+            Ok(_) => {}
+            Err(e) => {
+                eprintln!("Could not project the file {}: {}", file, e);
+                std::process::exit(1);
+            }
+        }
+    }
+
+    // Benchmark runs, reusing one output so allocation stays out of the timing
     let mut total_time = 0.0;
+    let mut scratch = ParsedCsv::with_capacity(pcsv.indexes.len());
 
     for _ in 0..args.iterations {
         let start = Instant::now();
-        let _ = parse_csv(buffer.data());
+        // The warmup run already succeeded on the same input
+        match &selection {
+            Some(selection) => {
+                let _ = project_csv(buffer.padded(), selection, args.parse.header);
+            }
+            None => {
+                let _ = parse_into_with(&mut scratch, buffer.padded(), &options);
+            }
+        }
         total_time += start.elapsed().as_secs_f64();
     }
 
     if args.dump {
         for (i, &idx) in pcsv.indexes.iter().enumerate() {
-            print!("{}: ", idx);
+            match &lines {
+                Some(lines) => {
+                    let pos = lines.position(idx as usize);
+                    print!("{}:{} {}: ", pos.line, pos.column, idx);
+                }
+                None => print!("{}: ", idx),
+            }
             if i < pcsv.indexes.len() - 1 {
                 let start = idx as usize;
                 let end = pcsv.indexes[i + 1] as usize;
                 if start < buffer.len() && end <= buffer.len() {
                     let field = &buffer.data()[start..end];
-                    if let Ok(s) = std::str::from_utf8(field) {
-                        print!("{}", s);
-                    }
+                    print!("{}", String::from_utf8_lossy(field));
                 }
             }
             println!();
@@ -101,3 +324,12 @@ fn main() {
         println!("[verbose] done");
     }
 }
+
+/// Read a `--columns` list as positions if every entry is a number, else as names
+fn parse_selection(list: &str) -> ColumnSelection {
+    let entries: Vec<&str> = list.split(',').map(str::trim).collect();
+    match entries.iter().map(|e| e.parse()).collect() {
+        Ok(indices) => ColumnSelection::Indices(indices),
+        Err(_) => ColumnSelection::Names(entries.iter().map(|e| e.to_string()).collect()),
+    }
+}
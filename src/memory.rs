@@ -1,24 +1,35 @@
 //! Memory utilities for aligned allocation
 
-use std::alloc::{alloc, dealloc, Layout};
+use crate::error::{Error, Result};
+use crate::CSV_PADDING;
+use std::alloc::{alloc_zeroed, dealloc, Layout};
 use std::ptr::NonNull;
 
-/// Allocate memory aligned to a specific boundary with padding
+/// Allocate zeroed memory aligned to a specific boundary with padding
+///
+/// Every byte of the allocation, padding included, is initialized to zero,
+/// so SIMD loads that run past `length` read well-defined memory.
 ///
 /// # Safety
 /// The returned pointer must be deallocated with `aligned_free`
 #[inline]
-pub fn allocate_padded_buffer(length: usize, padding: usize) -> Result<NonNull<u8>, String> {
-    let total_size = length + padding;
+pub fn allocate_padded_buffer(length: usize, padding: usize) -> Result<NonNull<u8>> {
+    let total_size = length.saturating_add(padding);
 
-    // Align to 64-byte boundary (cache line size)
-    let layout =
-        Layout::from_size_align(total_size, 64).map_err(|e| format!("Invalid layout: {}", e))?;
+    // Align to 64-byte boundary (cache line size). `extend` reports a
+    // `length + padding` that overflows as a layout error too
+    let layout = Layout::from_size_align(length, 64)
+        .and_then(|data| data.extend(Layout::from_size_align(padding, 1)?))
+        .map(|(layout, _)| layout)
+        .map_err(|source| Error::Layout {
+            size: total_size,
+            source,
+        })?;
 
     // SAFETY: We verify the layout is valid above
-    let ptr = unsafe { alloc(layout) };
+    let ptr = unsafe { alloc_zeroed(layout) };
 
-    NonNull::new(ptr).ok_or_else(|| "Failed to allocate memory".to_string())
+    NonNull::new(ptr).ok_or(Error::Allocation { size: total_size })
 }
 
 /// Free memory allocated with `allocate_padded_buffer`
@@ -34,6 +45,110 @@ pub unsafe fn aligned_free(ptr: NonNull<u8>, length: usize, padding: usize) {
     dealloc(ptr.as_ptr(), layout);
 }
 
+/// A byte buffer followed by at least `CSV_PADDING` zeroed bytes
+///
+/// Holding a `Padded<&[u8]>` proves that the `CSV_PADDING` bytes after the
+/// data are readable and zero, which is what lets the parser issue full
+/// 64-byte SIMD loads on the final block. Zero is neutral to the parser:
+/// it never matches a quote, separator or line terminator.
+#[derive(Clone, Copy, Debug)]
+pub struct Padded<T> {
+    inner: T,
+    length: usize,
+}
+
+impl<'a> Padded<&'a [u8]> {
+    /// Wrap a slice whose first `length` bytes are data and whose remaining
+    /// bytes are padding
+    ///
+    /// Fails if fewer than `CSV_PADDING` bytes follow the data or if any of
+    /// them is non-zero.
+    pub fn new(buf: &'a [u8], length: usize) -> Result<Self> {
+        if length > buf.len() {
+            return Err(Error::InvalidPadding { offset: buf.len() });
+        }
+        if buf.len() - length < CSV_PADDING {
+            return Err(Error::InvalidPadding { offset: buf.len() });
+        }
+        if let Some(pos) = buf[length..length + CSV_PADDING]
+            .iter()
+            .position(|&b| b != 0)
+        {
+            return Err(Error::InvalidPadding {
+                offset: length + pos,
+            });
+        }
+        Ok(Self { inner: buf, length })
+    }
+
+    /// Wrap a slice without checking its padding
+    ///
+    /// # Safety
+    /// `ptr` must point to `length + CSV_PADDING` initialized bytes whose last
+    /// `CSV_PADDING` bytes are zero, valid for the lifetime `'a`.
+    #[inline(always)]
+    pub(crate) unsafe fn from_raw_parts(ptr: *const u8, length: usize) -> Self {
+        Self {
+            inner: std::slice::from_raw_parts(ptr, length + CSV_PADDING),
+            length,
+        }
+    }
+
+    /// Get a slice view of the data (excluding padding)
+    #[inline(always)]
+    pub fn data(&self) -> &'a [u8] {
+        &self.inner[..self.length]
+    }
+
+    /// Get the data together with its padding
+    #[inline(always)]
+    pub fn with_padding(&self) -> &'a [u8] {
+        self.inner
+    }
+}
+
+impl Padded<Vec<u8>> {
+    /// Copy `data` into a new vector followed by `CSV_PADDING` zero bytes
+    pub fn from_slice(data: &[u8]) -> Self {
+        let mut inner = Vec::with_capacity(data.len() + CSV_PADDING);
+        inner.extend_from_slice(data);
+        inner.resize(data.len() + CSV_PADDING, 0);
+        Self {
+            inner,
+            length: data.len(),
+        }
+    }
+
+    /// Borrow as a `Padded<&[u8]>` for the parsing entry points
+    #[inline(always)]
+    pub fn as_padded(&self) -> Padded<&[u8]> {
+        Padded {
+            inner: &self.inner,
+            length: self.length,
+        }
+    }
+
+    /// Get a slice view of the data (excluding padding)
+    #[inline(always)]
+    pub fn data(&self) -> &[u8] {
+        &self.inner[..self.length]
+    }
+}
+
+impl<T> Padded<T> {
+    /// Get the length of the data (excluding padding)
+    #[inline(always)]
+    pub fn len(&self) -> usize {
+        self.length
+    }
+
+    /// Check if the data is empty
+    #[inline(always)]
+    pub fn is_empty(&self) -> bool {
+        self.length == 0
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -61,6 +176,59 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_layout_error() {
+        let result = allocate_padded_buffer(usize::MAX - 100, 64);
+        assert!(matches!(result, Err(Error::Layout { .. })));
+
+        // `length` alone is a valid layout, but adding the padding overflows
+        let result = allocate_padded_buffer(isize::MAX as usize - 100, 64);
+        assert!(matches!(result, Err(Error::Layout { .. })));
+
+        let result = allocate_padded_buffer(usize::MAX, 64);
+        assert!(matches!(
+            result,
+            Err(Error::Layout {
+                size: usize::MAX,
+                ..
+            })
+        ));
+    }
+
+    #[test]
+    fn test_allocation_is_zeroed() {
+        let ptr = allocate_padded_buffer(100, 64).unwrap();
+        unsafe {
+            let slice = std::slice::from_raw_parts(ptr.as_ptr(), 164);
+            assert!(slice.iter().all(|&b| b == 0));
+            aligned_free(ptr, 100, 64);
+        }
+    }
+
+    #[test]
+    fn test_padded_from_slice() {
+        let padded = Padded::from_slice(b"a,b\n");
+        assert_eq!(padded.len(), 4);
+        assert_eq!(padded.data(), b"a,b\n");
+        let view = padded.as_padded();
+        assert_eq!(view.with_padding().len(), 4 + CSV_PADDING);
+        assert!(view.with_padding()[4..].iter().all(|&b| b == 0));
+    }
+
+    #[test]
+    fn test_padded_new_checks_padding() {
+        let mut buf = vec![0u8; 10 + CSV_PADDING];
+        buf[..10].copy_from_slice(b"0123456789");
+        assert!(Padded::new(&buf, 10).is_ok());
+        assert!(Padded::new(&buf, 11).is_err());
+
+        buf[20] = b',';
+        assert!(matches!(
+            Padded::new(&buf, 10),
+            Err(Error::InvalidPadding { offset: 20 })
+        ));
+    }
+
     #[test]
     fn test_zero_length() {
         let result = allocate_padded_buffer(0, 64);
@@ -0,0 +1,236 @@
+//! Integer conversion of field bytes
+//!
+//! Up to 20 significant digits are right-aligned in a 32-byte block, checked
+//! to be digits with one compare per 16 bytes and combined with the
+//! multiply-add instructions, 2, 4 and then 8 digits at a time.
+
+use std::fmt;
+
+#[cfg(target_arch = "x86_64")]
+use std::arch::x86_64::*;
+
+/// Why a field is not a number
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum NumberError {
+    /// The field is empty
+    Empty,
+    /// The field holds a byte that does not belong in the number
+    InvalidDigit,
+    /// The value does not fit the target type
+    Overflow,
+}
+
+impl fmt::Display for NumberError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            NumberError::Empty => write!(f, "empty field"),
+            NumberError::InvalidDigit => write!(f, "invalid digit"),
+            NumberError::Overflow => write!(f, "number too large"),
+        }
+    }
+}
+
+impl std::error::Error for NumberError {}
+
+/// Converts at most 20 significant digits; picked once per column by
+/// `digits_kernel` so the CPU is not probed for every field
+pub(crate) type DigitsKernel = fn(&[u8]) -> Result<u64, NumberError>;
+
+/// Pick the fastest digit conversion this CPU supports
+pub(crate) fn digits_kernel() -> DigitsKernel {
+    #[cfg(target_arch = "x86_64")]
+    if is_x86_feature_detected!("sse4.1") {
+        return digits_sse41;
+    }
+    parse_digits_scalar
+}
+
+/// Convert `field` to `u64`, allowing a leading `+`
+pub fn parse_u64(field: &[u8]) -> Result<u64, NumberError> {
+    parse_u64_with(field, digits_kernel())
+}
+
+/// Convert `field` to `i64`, allowing a leading `+` or `-`
+pub fn parse_i64(field: &[u8]) -> Result<i64, NumberError> {
+    parse_i64_with(field, digits_kernel())
+}
+
+/// `parse_u64` with a kernel from `digits_kernel`
+pub(crate) fn parse_u64_with(field: &[u8], kernel: DigitsKernel) -> Result<u64, NumberError> {
+    match field {
+        [] => Err(NumberError::Empty),
+        [b'+', digits @ ..] => parse_digits(digits, kernel),
+        digits => parse_digits(digits, kernel),
+    }
+}
+
+/// `parse_i64` with a kernel from `digits_kernel`
+pub(crate) fn parse_i64_with(field: &[u8], kernel: DigitsKernel) -> Result<i64, NumberError> {
+    let (negative, magnitude) = match field {
+        [] => return Err(NumberError::Empty),
+        [b'-', digits @ ..] => (true, parse_digits(digits, kernel)?),
+        [b'+', digits @ ..] => (false, parse_digits(digits, kernel)?),
+        digits => (false, parse_digits(digits, kernel)?),
+    };
+    if negative {
+        match magnitude {
+            m if m <= i64::MAX as u64 => Ok(-(m as i64)),
+            m if m == i64::MIN.unsigned_abs() => Ok(i64::MIN),
+            _ => Err(NumberError::Overflow),
+        }
+    } else {
+        i64::try_from(magnitude).map_err(|_| NumberError::Overflow)
+    }
+}
+
+/// Convert unsigned ASCII digits; a bad digit wins over an overflow
+fn parse_digits(digits: &[u8], kernel: DigitsKernel) -> Result<u64, NumberError> {
+    if digits.is_empty() {
+        return Err(NumberError::InvalidDigit);
+    }
+    // Leading zeros do not count against the 20 digits of u64::MAX
+    let first = digits
+        .iter()
+        .position(|&b| b != b'0')
+        .unwrap_or(digits.len());
+    let significant = &digits[first..];
+
+    if significant.len() > 20 {
+        return if significant.iter().all(u8::is_ascii_digit) {
+            Err(NumberError::Overflow)
+        } else {
+            Err(NumberError::InvalidDigit)
+        };
+    }
+    kernel(significant)
+}
+
+fn parse_digits_scalar(digits: &[u8]) -> Result<u64, NumberError> {
+    if !digits.iter().all(u8::is_ascii_digit) {
+        return Err(NumberError::InvalidDigit);
+    }
+    digits.iter().try_fold(0u64, |acc, &b| {
+        acc.checked_mul(10)
+            .and_then(|acc| acc.checked_add((b - b'0') as u64))
+            .ok_or(NumberError::Overflow)
+    })
+}
+
+#[cfg(target_arch = "x86_64")]
+fn digits_sse41(digits: &[u8]) -> Result<u64, NumberError> {
+    // SAFETY: only handed out by `digits_kernel` once SSE4.1 is detected
+    unsafe { parse_digits_sse41(digits) }
+}
+
+/// Convert at most 20 digits
+#[cfg(target_arch = "x86_64")]
+#[target_feature(enable = "sse4.1")]
+unsafe fn parse_digits_sse41(digits: &[u8]) -> Result<u64, NumberError> {
+    // Pad on the left with '0' so the digits end at the last byte
+    let mut block = [b'0'; 32];
+    block[32 - digits.len()..].copy_from_slice(digits);
+
+    let zero = _mm_set1_epi8(b'0' as i8);
+    let hi = _mm_sub_epi8(_mm_loadu_si128(block.as_ptr() as *const __m128i), zero);
+    let lo = _mm_sub_epi8(
+        _mm_loadu_si128(block.as_ptr().add(16) as *const __m128i),
+        zero,
+    );
+
+    // Digits are the bytes that are at most 9 after the unsigned subtraction
+    let nine = _mm_set1_epi8(9);
+    let valid = _mm_and_si128(
+        _mm_cmpeq_epi8(_mm_max_epu8(hi, nine), nine),
+        _mm_cmpeq_epi8(_mm_max_epu8(lo, nine), nine),
+    );
+    if _mm_movemask_epi8(valid) != 0xFFFF {
+        return Err(NumberError::InvalidDigit);
+    }
+
+    // The high half holds at most 4 digits, so only the final step can overflow
+    sixteen_digits(hi)
+        .checked_mul(10_000_000_000_000_000)
+        .and_then(|v| v.checked_add(sixteen_digits(lo)))
+        .ok_or(NumberError::Overflow)
+}
+
+/// Combine 16 digit values, most significant first
+#[cfg(target_arch = "x86_64")]
+#[target_feature(enable = "sse4.1")]
+unsafe fn sixteen_digits(v: __m128i) -> u64 {
+    let pairs = _mm_maddubs_epi16(
+        v,
+        _mm_set_epi8(1, 10, 1, 10, 1, 10, 1, 10, 1, 10, 1, 10, 1, 10, 1, 10),
+    );
+    let quads = _mm_madd_epi16(pairs, _mm_set_epi16(1, 100, 1, 100, 1, 100, 1, 100));
+    let quads = _mm_packus_epi32(quads, quads);
+    let octets = _mm_madd_epi16(quads, _mm_set_epi16(1, 10000, 1, 10000, 1, 10000, 1, 10000));
+    let high = _mm_cvtsi128_si32(octets) as u32 as u64;
+    let low = _mm_extract_epi32::<1>(octets) as u32 as u64;
+    high * 100_000_000 + low
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_integers() {
+        assert_eq!(parse_u64(b"0"), Ok(0));
+        assert_eq!(parse_u64(b"+0042"), Ok(42));
+        assert_eq!(parse_u64(b"18446744073709551615"), Ok(u64::MAX));
+        assert_eq!(parse_u64(b"0000000000018446744073709551615"), Ok(u64::MAX));
+        assert_eq!(parse_i64(b"-9223372036854775808"), Ok(i64::MIN));
+        assert_eq!(parse_i64(b"9223372036854775807"), Ok(i64::MAX));
+        assert_eq!(parse_i64(b"-1234567890123456789"), Ok(-1234567890123456789));
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        assert_eq!(parse_u64(b""), Err(NumberError::Empty));
+        assert_eq!(parse_i64(b"-"), Err(NumberError::InvalidDigit));
+        assert_eq!(parse_u64(b"-1"), Err(NumberError::InvalidDigit));
+        assert_eq!(parse_i64(b" 1"), Err(NumberError::InvalidDigit));
+        assert_eq!(parse_i64(b"1.5"), Err(NumberError::InvalidDigit));
+        assert_eq!(parse_i64(b"12/"), Err(NumberError::InvalidDigit));
+        assert_eq!(parse_i64(b"12:"), Err(NumberError::InvalidDigit));
+        assert_eq!(
+            parse_u64(b"18446744073709551616"),
+            Err(NumberError::Overflow)
+        );
+        assert_eq!(
+            parse_i64(b"9223372036854775808"),
+            Err(NumberError::Overflow)
+        );
+        assert_eq!(
+            parse_i64(b"-9223372036854775809"),
+            Err(NumberError::Overflow)
+        );
+        assert_eq!(
+            parse_u64(b"123456789012345678901"),
+            Err(NumberError::Overflow)
+        );
+        assert_eq!(
+            parse_u64(b"12345678901234567890x"),
+            Err(NumberError::InvalidDigit)
+        );
+    }
+
+    #[test]
+    fn test_simd_matches_scalar() {
+        let mut value = 1u64;
+        for len in 1..=20 {
+            for delta in [0u64, 1, 7, 99] {
+                let text = value.wrapping_add(delta).to_string();
+                let digits = &text.as_bytes()[..len.min(text.len())];
+                assert_eq!(
+                    parse_digits(digits, digits_kernel()),
+                    parse_digits_scalar(digits),
+                    "{:?}",
+                    text
+                );
+            }
+            value = value.wrapping_mul(10).wrapping_add(len as u64 % 10);
+        }
+    }
+}
@@ -1,6 +1,17 @@
 //! CSV parser with SIMD acceleration
 
+use crate::error::{Error, Limit, ParseErrorKind, Result};
+use crate::limits::{max_indexes, LimitChecker, Limits};
+use crate::memory::Padded;
 use crate::portability::{hamming, trailing_zeros};
+use crate::ragged::{check_field_counts, RaggedMode, RaggedRow};
+use crate::recovery::{recover, RecordError, RecoveryOptions};
+#[cfg(any(target_arch = "x86_64", target_arch = "aarch64"))]
+use crate::strict::TextDataChecker;
+use crate::strict::{check_textdata_scalar, Violation};
+use crate::utf8::invalid_utf8_error;
+#[cfg(any(target_arch = "x86_64", target_arch = "aarch64"))]
+use crate::utf8::Utf8Checker;
 
 #[cfg(target_arch = "x86_64")]
 use std::arch::x86_64::*;
@@ -8,12 +19,27 @@ use std::arch::x86_64::*;
 #[cfg(target_arch = "aarch64")]
 use std::arch::aarch64::*;
 
+/// Largest input the parser accepts, since indexes are stored as `u32`
+pub const MAX_INPUT_LEN: usize = u32::MAX as usize;
+
 /// Parsed CSV structure containing field separator indexes
 ///
 /// Uses a chunked allocation strategy to amortize allocation costs
 /// and reduce the need for frequent reallocations.
 pub struct ParsedCsv {
     pub indexes: Vec<u32>,
+    /// TEXTDATA violations found in strict mode, in input order
+    pub violations: Vec<Violation>,
+    /// Records whose field count differs from the first, in skip and report modes
+    pub ragged_rows: Vec<RaggedRow>,
+    /// Problems skipped over in recovery mode, in input order
+    pub errors: Vec<RecordError>,
+    /// Whether `records` leaves out `ragged_rows`
+    pub(crate) skip_ragged: bool,
+    /// Whether the first record is a header
+    pub(crate) has_header: bool,
+    /// Limits of the parse, also applied to structures built from it
+    pub(crate) limits: Limits,
     chunk_size: usize,
 }
 
@@ -24,19 +50,46 @@ impl ParsedCsv {
         let chunk_size = 1024.min(capacity / 4).max(64);
         Self {
             indexes: Vec::with_capacity(capacity),
+            violations: Vec::new(),
+            ragged_rows: Vec::new(),
+            errors: Vec::new(),
+            skip_ragged: false,
+            has_header: false,
+            limits: Limits::default(),
             chunk_size,
         }
     }
 
+    /// Remove all indexes and diagnostics while keeping the allocated capacity
+    #[inline]
+    pub fn clear(&mut self) {
+        self.indexes.clear();
+        self.violations.clear();
+        self.ragged_rows.clear();
+        self.errors.clear();
+        self.skip_ragged = false;
+        self.has_header = false;
+        self.limits = Limits::default();
+    }
+
     /// Ensure we have capacity for at least n more elements
     /// This amortizes allocation cost by allocating in chunks
     #[inline(always)]
     fn ensure_capacity(&mut self, additional: usize) {
-        let available = self.indexes.capacity() - self.indexes.len();
+        let len = self.indexes.len();
+        let available = self.indexes.capacity() - len;
         if available < additional {
             // Allocate in chunks to reduce allocation frequency
             let to_reserve = additional.max(self.chunk_size);
-            self.indexes.reserve(to_reserve);
+            // `reserve` may double the capacity; never past the index limit
+            let max = max_indexes(&self.limits);
+            let grown = (2 * self.indexes.capacity()).max(len + to_reserve);
+            if grown > max {
+                self.indexes
+                    .reserve_exact(additional.max(max.saturating_sub(len)));
+            } else {
+                self.indexes.reserve(to_reserve);
+            }
         }
     }
 }
@@ -44,24 +97,24 @@ impl ParsedCsv {
 /// SIMD input structure for processing 64 bytes at a time
 #[cfg(target_arch = "x86_64")]
 #[derive(Clone, Copy)]
-struct SimdInput {
-    lo: __m256i,
-    hi: __m256i,
+pub(crate) struct SimdInput {
+    pub(crate) lo: __m256i,
+    pub(crate) hi: __m256i,
 }
 
 #[cfg(target_arch = "aarch64")]
 #[derive(Clone, Copy)]
-struct SimdInput {
-    i0: uint8x16_t,
-    i1: uint8x16_t,
-    i2: uint8x16_t,
-    i3: uint8x16_t,
+pub(crate) struct SimdInput {
+    pub(crate) i0: uint8x16_t,
+    pub(crate) i1: uint8x16_t,
+    pub(crate) i2: uint8x16_t,
+    pub(crate) i3: uint8x16_t,
 }
 
 /// Fill SIMD input from buffer
 #[cfg(target_arch = "x86_64")]
 #[inline(always)]
-unsafe fn fill_input(ptr: *const u8) -> SimdInput {
+pub(crate) unsafe fn fill_input(ptr: *const u8) -> SimdInput {
     SimdInput {
         lo: _mm256_loadu_si256(ptr as *const __m256i),
         hi: _mm256_loadu_si256(ptr.add(32) as *const __m256i),
@@ -70,7 +123,7 @@ unsafe fn fill_input(ptr: *const u8) -> SimdInput {
 
 #[cfg(target_arch = "aarch64")]
 #[inline(always)]
-unsafe fn fill_input(ptr: *const u8) -> SimdInput {
+pub(crate) unsafe fn fill_input(ptr: *const u8) -> SimdInput {
     SimdInput {
         i0: vld1q_u8(ptr),
         i1: vld1q_u8(ptr.add(16)),
@@ -82,7 +135,7 @@ unsafe fn fill_input(ptr: *const u8) -> SimdInput {
 /// Compare all bytes in SIMD input against a mask value
 #[cfg(target_arch = "x86_64")]
 #[inline(always)]
-unsafe fn cmp_mask_against_input(input: SimdInput, mask: u8) -> u64 {
+pub(crate) unsafe fn cmp_mask_against_input(input: SimdInput, mask: u8) -> u64 {
     let mask_vec = _mm256_set1_epi8(mask as i8);
     let cmp_res_0 = _mm256_cmpeq_epi8(input.lo, mask_vec);
     let res_0 = _mm256_movemask_epi8(cmp_res_0) as u32 as u64;
@@ -93,7 +146,7 @@ unsafe fn cmp_mask_against_input(input: SimdInput, mask: u8) -> u64 {
 
 #[cfg(target_arch = "aarch64")]
 #[inline(always)]
-unsafe fn cmp_mask_against_input(input: SimdInput, mask: u8) -> u64 {
+pub(crate) unsafe fn cmp_mask_against_input(input: SimdInput, mask: u8) -> u64 {
     let mask_vec = vdupq_n_u8(mask);
     let cmp_res_0 = vceqq_u8(input.i0, mask_vec);
     let cmp_res_1 = vceqq_u8(input.i1, mask_vec);
@@ -104,7 +157,7 @@ unsafe fn cmp_mask_against_input(input: SimdInput, mask: u8) -> u64 {
 
 #[cfg(target_arch = "aarch64")]
 #[inline(always)]
-unsafe fn neon_movemask_bulk(
+pub(crate) unsafe fn neon_movemask_bulk(
     i0: uint8x16_t,
     i1: uint8x16_t,
     i2: uint8x16_t,
@@ -146,7 +199,7 @@ unsafe fn neon_movemask(input: uint8x16_t) -> u16 {
 /// Find quote mask using carryless multiplication
 #[cfg(target_arch = "x86_64")]
 #[inline(always)]
-unsafe fn find_quote_mask(input: SimdInput, prev_iter_inside_quote: &mut u64) -> u64 {
+pub(crate) unsafe fn find_quote_mask(input: SimdInput, prev_iter_inside_quote: &mut u64) -> u64 {
     let quote_bits = cmp_mask_against_input(input, b'"');
 
     // Use carryless multiply to find quote regions
@@ -166,7 +219,7 @@ unsafe fn find_quote_mask(input: SimdInput, prev_iter_inside_quote: &mut u64) ->
 
 #[cfg(target_arch = "aarch64")]
 #[inline(always)]
-unsafe fn find_quote_mask(input: SimdInput, prev_iter_inside_quote: &mut u64) -> u64 {
+pub(crate) unsafe fn find_quote_mask(input: SimdInput, prev_iter_inside_quote: &mut u64) -> u64 {
     let quote_bits = cmp_mask_against_input(input, b'"');
 
     // Use polynomial multiplication for ARM
@@ -201,38 +254,97 @@ fn flatten_bits(pcsv: &mut ParsedCsv, idx: u32, mut bits: u64) {
     }
 }
 
+/// Quote state carried between successive scans of one input
+///
+/// Lets a stream be scanned piece by piece: each call to
+/// `find_indexes_resumable` picks up the quote state where the previous call
+/// on the same input left off.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct ParserState {
+    prev_iter_inside_quote: u64,
+    /// Limits and the counts they apply to, carried between scans
+    pub(crate) limits: LimitChecker,
+}
+
+impl ParserState {
+    /// Create the state for the start of an input
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Create the state for the start of an input whose scans enforce `limits`
+    pub fn with_limits(limits: &Limits) -> Self {
+        Self {
+            prev_iter_inside_quote: 0,
+            limits: LimitChecker::new(limits),
+        }
+    }
+
+    /// Continue outside any quoted field, as at the start of an input
+    #[inline(always)]
+    pub(crate) fn reset_quote(&mut self) {
+        self.prev_iter_inside_quote = 0;
+    }
+
+    /// Check if the last scanned byte was inside a quoted field
+    #[inline(always)]
+    pub fn inside_quote(&self) -> bool {
+        self.prev_iter_inside_quote != 0
+    }
+}
+
 /// Parse CSV buffer and find field separator indexes
 ///
 /// # Safety
 ///
 /// This function must only be called on x86_64 CPUs that support AVX2 and PCLMULQDQ.
-/// The caller must ensure:
-/// - The buffer is valid and accessible for the duration of the call
-/// - The buffer is properly aligned for SIMD operations
 ///
 /// The function uses SIMD intrinsics that require proper CPU feature support.
-/// Note: Buffers with less than 64 bytes are handled separately by the scalar fallback.
+/// The final partial block is loaded with a full 64-byte read that runs into
+/// the zeroed padding guaranteed by `Padded`.
 #[cfg(target_arch = "x86_64")]
 #[target_feature(enable = "avx2")]
 #[target_feature(enable = "pclmulqdq")]
-pub unsafe fn find_indexes_avx2(buf: &[u8], pcsv: &mut ParsedCsv) -> bool {
-    let len = buf.len();
-    let mut prev_iter_inside_quote = 0u64;
-
-    if len < 64 {
-        return true;
-    }
+pub unsafe fn find_indexes_avx2(buf: Padded<&[u8]>, pcsv: &mut ParsedCsv) -> bool {
+    find_indexes_avx2_from::<0>(buf, 0, buf.len(), pcsv, &mut ParserState::new())
+}
 
-    let lenminus64 = len - 64;
-    let mut idx = 0;
+/// AVX2 scan of bytes `start..end` of `buf` with a carried quote state
+///
+/// `PASSES` selects the passes fused into the loop. With `PASS_UTF8` each
+/// block is also fed to the UTF-8 checker, and the return value reports
+/// whether the input was valid. With `PASS_STRICT` TEXTDATA violations are
+/// appended to `pcsv.violations`. With `PASS_LIMITS` the scan stops at the
+/// first block that breaks a limit, leaving the breach in `state.limits`.
+///
+/// # Safety
+///
+/// Same requirements as `find_indexes_avx2`.
+#[cfg(target_arch = "x86_64")]
+#[target_feature(enable = "avx2")]
+#[target_feature(enable = "pclmulqdq")]
+unsafe fn find_indexes_avx2_from<const PASSES: u8>(
+    buf: Padded<&[u8]>,
+    start: usize,
+    end: usize,
+    pcsv: &mut ParsedCsv,
+    state: &mut ParserState,
+) -> bool {
+    let len = end;
+    let ptr = buf.with_padding().as_ptr();
+    let mut prev_iter_inside_quote = state.prev_iter_inside_quote;
+    let mut idx = start;
+    let mut utf8 = Utf8Checker::new();
+    let mut textdata = TextDataChecker::default();
 
     // Buffered processing for better pipelining
     const BUFFER_SIZE: usize = 4;
 
-    if lenminus64 > 64 * BUFFER_SIZE {
+    if len > 64 * BUFFER_SIZE {
         let mut fields = [0u64; BUFFER_SIZE];
+        let mut ends = [0u64; BUFFER_SIZE];
 
-        while idx < lenminus64.saturating_sub(64 * BUFFER_SIZE - 1) {
+        while idx + 64 * BUFFER_SIZE <= len {
             // Process BUFFER_SIZE chunks and store results
             #[allow(clippy::needless_range_loop)]
             for b in 0..BUFFER_SIZE {
@@ -241,22 +353,48 @@ pub unsafe fn find_indexes_avx2(buf: &[u8], pcsv: &mut ParsedCsv) -> bool {
                 // Prefetch for next iteration
                 #[cfg(target_arch = "x86_64")]
                 {
-                    let prefetch_ptr = buf.as_ptr().add(internal_idx + 128);
+                    let prefetch_ptr = ptr.wrapping_add(internal_idx + 128);
                     _mm_prefetch(prefetch_ptr as *const i8, _MM_HINT_T0);
                 }
 
-                let input = fill_input(buf.as_ptr().add(internal_idx));
+                let input = fill_input(ptr.add(internal_idx));
+                if PASSES & PASS_UTF8 != 0 {
+                    utf8.check_block(input);
+                }
                 let quote_mask = find_quote_mask(input, &mut prev_iter_inside_quote);
                 let sep = cmp_mask_against_input(input, b',');
                 let end = cmp_mask_against_input(input, b'\n');
+                if PASSES & PASS_STRICT != 0 {
+                    textdata.check_block(
+                        input,
+                        internal_idx,
+                        len,
+                        quote_mask,
+                        end,
+                        &mut pcsv.violations,
+                    );
+                }
 
                 fields[b] = (end | sep) & !quote_mask;
+                if PASSES & PASS_LIMITS != 0 {
+                    ends[b] = end & !quote_mask;
+                }
             }
 
             // Flatten all buffered results
             #[allow(clippy::needless_range_loop)]
             for b in 0..BUFFER_SIZE {
                 let internal_idx = 64 * b + idx;
+                if PASSES & PASS_LIMITS != 0 {
+                    let indexes = pcsv.indexes.len() + hamming(fields[b]) as usize;
+                    if !state
+                        .limits
+                        .check_block(internal_idx, len, fields[b], ends[b], indexes)
+                    {
+                        // The caller reports the breach before anything else
+                        return true;
+                    }
+                }
                 flatten_bits(pcsv, internal_idx as u32, fields[b]);
             }
 
@@ -264,87 +402,356 @@ pub unsafe fn find_indexes_avx2(buf: &[u8], pcsv: &mut ParsedCsv) -> bool {
         }
     }
 
-    // Process remaining chunks
-    while idx < lenminus64 {
-        let input = fill_input(buf.as_ptr().add(idx));
+    // Process remaining chunks; the last one reads into the zeroed padding,
+    // which never matches a quote or separator
+    while idx < len {
+        let input = fill_input(ptr.add(idx));
+        if PASSES & PASS_UTF8 != 0 {
+            utf8.check_block(input);
+        }
         let quote_mask = find_quote_mask(input, &mut prev_iter_inside_quote);
         let sep = cmp_mask_against_input(input, b',');
         let end = cmp_mask_against_input(input, b'\n');
+        if PASSES & PASS_STRICT != 0 {
+            textdata.check_block(input, idx, len, quote_mask, end, &mut pcsv.violations);
+        }
 
         let field_sep = (end | sep) & !quote_mask;
+        if PASSES & PASS_LIMITS != 0 {
+            let indexes = pcsv.indexes.len() + hamming(field_sep) as usize;
+            if !state
+                .limits
+                .check_block(idx, len, field_sep, end & !quote_mask, indexes)
+            {
+                return true;
+            }
+        }
         flatten_bits(pcsv, idx as u32, field_sep);
 
         idx += 64;
     }
 
-    // Process remaining bytes with scalar fallback
-    let in_quote_start = prev_iter_inside_quote != 0;
-    process_tail_scalar(&buf[idx..], idx, pcsv, in_quote_start);
+    if PASSES & PASS_STRICT != 0 {
+        textdata.finish(len, &mut pcsv.violations);
+    }
+    state.prev_iter_inside_quote = prev_iter_inside_quote;
+    PASSES & PASS_UTF8 == 0 || utf8.finish()
+}
 
-    true
+/// Options for the optional passes run alongside the separator scan
+#[derive(Clone, Debug, Default)]
+pub struct ParseOptions {
+    /// Validate that the input is UTF-8 in the same pass
+    pub validate_utf8: bool,
+    /// Record bytes outside quoted fields that RFC 4180 TEXTDATA does not
+    /// allow (non-ASCII, control bytes, bare CR) in `ParsedCsv::violations`
+    pub strict: bool,
+    /// How to handle records whose field count differs from the first record
+    pub ragged: RaggedMode,
+    /// Resynchronize after runaway quoted fields instead of failing
+    pub recovery: Option<RecoveryOptions>,
+    /// Bounds on field length, fields, records and index memory
+    pub limits: Limits,
+    /// Treat the first record as a header, returned by `ParsedCsv::headers`
+    /// instead of `ParsedCsv::records`
+    pub has_header: bool,
 }
 
-/// Parse CSV buffer (x86_64 with runtime feature detection)
-#[cfg(target_arch = "x86_64")]
-pub fn find_indexes(buf: &[u8], pcsv: &mut ParsedCsv) -> bool {
-    if is_x86_feature_detected!("avx2") && is_x86_feature_detected!("pclmulqdq") {
-        unsafe { find_indexes_avx2(buf, pcsv) }
+/// Parse CSV buffer using the best implementation for this CPU
+///
+/// Fails if the input is too large for `u32` indexes or ends inside a
+/// quoted field.
+pub fn find_indexes(buf: Padded<&[u8]>, pcsv: &mut ParsedCsv) -> Result<()> {
+    find_indexes_with(buf, pcsv, &ParseOptions::default())
+}
+
+/// Parse CSV buffer, running the passes enabled in `options`
+pub fn find_indexes_with(
+    buf: Padded<&[u8]>,
+    pcsv: &mut ParsedCsv,
+    options: &ParseOptions,
+) -> Result<()> {
+    check_input_len(buf.len())?;
+    let before = pcsv.indexes.len();
+    pcsv.limits = options.limits.clone();
+    let mut state = if options.recovery.is_some() {
+        // Recovery checks field lengths once runaway quotes are cut
+        ParserState::with_limits(&Limits {
+            max_field_len: None,
+            ..options.limits.clone()
+        })
     } else {
-        find_indexes_fallback(buf, pcsv)
+        ParserState::with_limits(&options.limits)
+    };
+
+    let mut passes = 0;
+    if options.validate_utf8 {
+        passes |= PASS_UTF8;
+    }
+    if options.strict {
+        passes |= PASS_STRICT;
+    }
+    let utf8_valid = scan_checked(buf, 0, buf.len(), pcsv, &mut state, passes)?;
+    if options.recovery.is_none() {
+        if let Some(err) = state.limits.finish(buf.len()) {
+            return Err(err);
+        }
+    }
+    if !utf8_valid {
+        return Err(invalid_utf8_error(buf.data()));
     }
+
+    if let Some(recovery) = &options.recovery {
+        // Recovery may split records, so the record count is final only now
+        recover(
+            buf,
+            pcsv,
+            before,
+            &mut state,
+            recovery,
+            options.limits.max_field_len,
+        )?;
+        if let Some(err) = state.limits.finish(buf.len()) {
+            return Err(err);
+        }
+    } else if state.inside_quote() {
+        // Everything after the last separator belongs to the open field
+        let offset = pcsv.indexes[before..]
+            .last()
+            .map_or(0, |&idx| idx as usize + 1);
+        return Err(Error::Parse {
+            offset,
+            kind: ParseErrorKind::UnterminatedQuote,
+        });
+    }
+    pcsv.has_header = options.has_header;
+    check_field_counts(buf.data(), pcsv, before, options.ragged)
 }
 
-/// Parse CSV buffer (ARM NEON)
-#[cfg(target_arch = "aarch64")]
-pub fn find_indexes(buf: &[u8], pcsv: &mut ParsedCsv) -> bool {
-    let len = buf.len();
-    let mut prev_iter_inside_quote = 0u64;
+/// Parse CSV buffer from byte `start`, continuing from a previous scan
+///
+/// Indexes are pushed relative to the start of `buf`, and `state` is
+/// updated so the next call can continue from `buf.len()`. An open quote
+/// at the end is not an error here, since more input may follow. The limits
+/// of a state from `ParserState::with_limits` apply across all the calls.
+pub fn find_indexes_resumable(
+    buf: Padded<&[u8]>,
+    start: usize,
+    pcsv: &mut ParsedCsv,
+    state: &mut ParserState,
+) -> Result<()> {
+    check_input_len(buf.len())?;
+    scan_checked(buf, start, buf.len(), pcsv, state, 0)?;
+    Ok(())
+}
 
-    if len < 64 {
-        process_tail_scalar(buf, 0, pcsv, false);
-        return true;
+/// Scan bytes `start..end` with the limits in `state` checked on top of
+/// `passes`, returning whether UTF-8 validation passed
+///
+/// `end` is either `buf.len()` or a multiple of 64 bytes past `start`, so
+/// no block reads past it.
+pub(crate) fn scan_checked(
+    buf: Padded<&[u8]>,
+    start: usize,
+    end: usize,
+    pcsv: &mut ParsedCsv,
+    state: &mut ParserState,
+    mut passes: u8,
+) -> Result<bool> {
+    if state.limits.is_active() {
+        passes |= PASS_LIMITS;
     }
+    let utf8_valid = scan_indexes(buf, start, end, pcsv, state, passes);
+    match state.limits.take_error() {
+        Some(err) => Err(err),
+        None => Ok(utf8_valid),
+    }
+}
 
-    let lenminus64 = len - 64;
-    let mut idx = 0;
+/// Passes fused into a scan, as bit flags for the `PASSES` parameter
+const PASS_UTF8: u8 = 1;
+const PASS_STRICT: u8 = 2;
+const PASS_LIMITS: u8 = 4;
+
+/// Call `$scan::<PASSES>` with runtime pass flags turned into a constant
+macro_rules! dispatch_passes {
+    ($passes:expr, $scan:ident($($arg:expr),*)) => {
+        match $passes {
+            0 => $scan::<0>($($arg),*),
+            1 => $scan::<1>($($arg),*),
+            2 => $scan::<2>($($arg),*),
+            3 => $scan::<3>($($arg),*),
+            4 => $scan::<4>($($arg),*),
+            5 => $scan::<5>($($arg),*),
+            6 => $scan::<6>($($arg),*),
+            _ => $scan::<7>($($arg),*),
+        }
+    };
+}
+
+pub(crate) fn check_input_len(len: usize) -> Result<()> {
+    if len > MAX_INPUT_LEN {
+        return Err(Error::LimitExceeded {
+            limit: Limit::InputLength,
+            max: MAX_INPUT_LEN,
+            offset: MAX_INPUT_LEN,
+        });
+    }
+    Ok(())
+}
 
-    // Main processing loop
+/// Scan bytes `start..end` (x86_64 with runtime feature detection)
+///
+/// Returns false if UTF-8 validation was requested and failed. Validation
+/// and strict mode only make sense when scanning a whole input from the
+/// start.
+#[cfg(target_arch = "x86_64")]
+fn scan_indexes(
+    buf: Padded<&[u8]>,
+    start: usize,
+    end: usize,
+    pcsv: &mut ParsedCsv,
+    state: &mut ParserState,
+    passes: u8,
+) -> bool {
+    if is_x86_feature_detected!("avx2") && is_x86_feature_detected!("pclmulqdq") {
+        unsafe { dispatch_passes!(passes, find_indexes_avx2_from(buf, start, end, pcsv, state)) }
+    } else {
+        find_indexes_fallback(buf, start, end, pcsv, state, passes)
+    }
+}
+
+/// Scan bytes `start..end` (ARM NEON)
+#[cfg(target_arch = "aarch64")]
+fn scan_indexes(
+    buf: Padded<&[u8]>,
+    start: usize,
+    end: usize,
+    pcsv: &mut ParsedCsv,
+    state: &mut ParserState,
+    passes: u8,
+) -> bool {
+    dispatch_passes!(passes, scan_indexes_neon(buf, start, end, pcsv, state))
+}
+
+#[cfg(target_arch = "aarch64")]
+fn scan_indexes_neon<const PASSES: u8>(
+    buf: Padded<&[u8]>,
+    start: usize,
+    end: usize,
+    pcsv: &mut ParsedCsv,
+    state: &mut ParserState,
+) -> bool {
+    let len = end;
+    let ptr = buf.with_padding().as_ptr();
+    let mut prev_iter_inside_quote = state.prev_iter_inside_quote;
+    let mut idx = start;
+
+    // Main processing loop; the last block reads into the zeroed padding
     unsafe {
-        while idx < lenminus64 {
-            let input = fill_input(buf.as_ptr().add(idx));
+        let mut utf8 = Utf8Checker::new();
+        let mut textdata = TextDataChecker::default();
+        while idx < len {
+            let input = fill_input(ptr.add(idx));
+            if PASSES & PASS_UTF8 != 0 {
+                utf8.check_block(input);
+            }
             let quote_mask = find_quote_mask(input, &mut prev_iter_inside_quote);
             let sep = cmp_mask_against_input(input, b',');
             let end = cmp_mask_against_input(input, b'\n');
+            if PASSES & PASS_STRICT != 0 {
+                textdata.check_block(input, idx, len, quote_mask, end, &mut pcsv.violations);
+            }
 
             let field_sep = (end | sep) & !quote_mask;
+            if PASSES & PASS_LIMITS != 0 {
+                let indexes = pcsv.indexes.len() + hamming(field_sep) as usize;
+                if !state
+                    .limits
+                    .check_block(idx, len, field_sep, end & !quote_mask, indexes)
+                {
+                    return true;
+                }
+            }
             flatten_bits(pcsv, idx as u32, field_sep);
 
             idx += 64;
         }
-    }
 
-    // Process remaining bytes with scalar fallback
-    let in_quote_start = prev_iter_inside_quote != 0;
-    process_tail_scalar(&buf[idx..], idx, pcsv, in_quote_start);
-
-    true
+        if PASSES & PASS_STRICT != 0 {
+            textdata.finish(len, &mut pcsv.violations);
+        }
+        state.prev_iter_inside_quote = prev_iter_inside_quote;
+        PASSES & PASS_UTF8 == 0 || utf8.finish()
+    }
 }
 
-/// Parse CSV buffer (fallback for unsupported architectures)
+/// Scan bytes `start..end` (fallback for unsupported architectures)
 #[cfg(not(any(target_arch = "x86_64", target_arch = "aarch64")))]
-pub fn find_indexes(buf: &[u8], pcsv: &mut ParsedCsv) -> bool {
-    find_indexes_fallback(buf, pcsv)
+fn scan_indexes(
+    buf: Padded<&[u8]>,
+    start: usize,
+    end: usize,
+    pcsv: &mut ParsedCsv,
+    state: &mut ParserState,
+    passes: u8,
+) -> bool {
+    find_indexes_fallback(buf, start, end, pcsv, state, passes)
 }
 
 /// Scalar fallback implementation
-fn find_indexes_fallback(buf: &[u8], pcsv: &mut ParsedCsv) -> bool {
-    process_tail_scalar(buf, 0, pcsv, false);
-    true
+#[cfg_attr(target_arch = "aarch64", allow(dead_code))]
+fn find_indexes_fallback(
+    buf: Padded<&[u8]>,
+    start: usize,
+    end: usize,
+    pcsv: &mut ParsedCsv,
+    state: &mut ParserState,
+    passes: u8,
+) -> bool {
+    let data = &buf.data()[start..end];
+    if passes & PASS_STRICT != 0 {
+        check_textdata_scalar(data, &mut pcsv.violations);
+    }
+
+    let mut in_quote = state.inside_quote();
+    if passes & PASS_LIMITS != 0 {
+        // Go block by block so limits are checked as the indexes grow
+        for (n, block) in data.chunks(64).enumerate() {
+            let idx = start + 64 * n;
+            let before = pcsv.indexes.len();
+            pcsv.ensure_capacity(block.len());
+            in_quote = process_tail_scalar(block, idx, pcsv, in_quote);
+
+            let (mut seps, mut ends) = (0u64, 0u64);
+            for &sep in &pcsv.indexes[before..] {
+                let bit = 1u64 << (sep as usize - idx);
+                seps |= bit;
+                if buf.data()[sep as usize] == b'\n' {
+                    ends |= bit;
+                }
+            }
+            let indexes = pcsv.indexes.len();
+            if !state.limits.check_block(idx, end, seps, ends, indexes) {
+                return true;
+            }
+        }
+    } else {
+        in_quote = process_tail_scalar(data, start, pcsv, in_quote);
+    }
+    state.prev_iter_inside_quote = if in_quote { !0 } else { 0 };
+    passes & PASS_UTF8 == 0 || std::str::from_utf8(data).is_ok()
 }
 
-/// Process remaining bytes with scalar code
+/// Process remaining bytes with scalar code, returning the final quote state
 #[inline(always)]
-fn process_tail_scalar(buf: &[u8], offset: usize, pcsv: &mut ParsedCsv, mut in_quote: bool) {
+#[cfg_attr(target_arch = "aarch64", allow(dead_code))]
+fn process_tail_scalar(
+    buf: &[u8],
+    offset: usize,
+    pcsv: &mut ParsedCsv,
+    mut in_quote: bool,
+) -> bool {
     for (i, &byte) in buf.iter().enumerate() {
         match byte {
             b'"' => in_quote = !in_quote,
@@ -352,13 +759,54 @@ fn process_tail_scalar(buf: &[u8], offset: usize, pcsv: &mut ParsedCsv, mut in_q
             _ => {}
         }
     }
+    in_quote
 }
 
 /// Parse CSV file
-pub fn parse_csv(buf: &[u8]) -> ParsedCsv {
-    let mut pcsv = ParsedCsv::with_capacity(buf.len() / 10); // Estimate
-    find_indexes(buf, &mut pcsv);
-    pcsv
+pub fn parse_csv(buf: Padded<&[u8]>) -> Result<ParsedCsv> {
+    parse_csv_with(buf, &ParseOptions::default())
+}
+
+/// Parse CSV file with the passes enabled in `options`
+pub fn parse_csv_with(buf: Padded<&[u8]>, options: &ParseOptions) -> Result<ParsedCsv> {
+    // Estimate, but never more than the index memory limit allows
+    let capacity = (buf.len() / 10).min(max_indexes(&options.limits));
+    let mut pcsv = ParsedCsv::with_capacity(capacity);
+    find_indexes_with(buf, &mut pcsv, options)?;
+    Ok(pcsv)
+}
+
+/// Parse CSV file into an existing `ParsedCsv`, reusing its allocation
+///
+/// Any previous indexes and diagnostics are discarded.
+pub fn parse_into(pcsv: &mut ParsedCsv, buf: Padded<&[u8]>) -> Result<()> {
+    parse_into_with(pcsv, buf, &ParseOptions::default())
+}
+
+/// Parse CSV file into an existing `ParsedCsv` with the passes enabled in `options`
+pub fn parse_into_with(
+    pcsv: &mut ParsedCsv,
+    buf: Padded<&[u8]>,
+    options: &ParseOptions,
+) -> Result<()> {
+    pcsv.clear();
+    find_indexes_with(buf, pcsv, options)
+}
+
+/// Parse CSV file, validating UTF-8 in the same pass
+///
+/// Returns the input as `&str` so records can be read through
+/// `ParsedCsv::str_records` without validating again.
+pub fn parse_csv_utf8<'a>(
+    buf: Padded<&'a [u8]>,
+    options: &ParseOptions,
+) -> Result<(ParsedCsv, &'a str)> {
+    let mut options = options.clone();
+    options.validate_utf8 = true;
+    let pcsv = parse_csv_with(buf, &options)?;
+    // SAFETY: the scan above validated the whole input as UTF-8
+    let text = unsafe { std::str::from_utf8_unchecked(buf.data()) };
+    Ok((pcsv, text))
 }
 
 #[cfg(test)]
@@ -373,7 +821,7 @@ mod tests {
             data.extend_from_slice(format!("field{},value{}\n", i, i).as_bytes());
         }
 
-        let pcsv = parse_csv(&data);
+        let pcsv = parse_csv(Padded::from_slice(&data).as_padded()).unwrap();
 
         // Should find commas and newlines
         assert!(!pcsv.indexes.is_empty());
@@ -394,7 +842,7 @@ mod tests {
             data.extend_from_slice(format!("\"field,{}\",value{}\n", i, i).as_bytes());
         }
 
-        let pcsv = parse_csv(&data);
+        let pcsv = parse_csv(Padded::from_slice(&data).as_padded()).unwrap();
 
         // Should find separators but not commas inside quotes
         assert!(!pcsv.indexes.is_empty());
@@ -409,15 +857,170 @@ mod tests {
 
     #[test]
     fn test_parse_empty() {
-        let data = b"";
-        let pcsv = parse_csv(data);
+        let pcsv = parse_csv(Padded::from_slice(b"").as_padded()).unwrap();
         assert!(pcsv.indexes.is_empty());
     }
 
+    #[test]
+    fn test_parse_short_input() {
+        // Inputs shorter than one SIMD block are read through the padding
+        let pcsv = parse_csv(Padded::from_slice(b"a,b\n\"c,d\",e\n").as_padded()).unwrap();
+        assert_eq!(pcsv.indexes, vec![1, 3, 9, 11]);
+    }
+
+    #[test]
+    fn test_parse_matches_scalar() {
+        let mut data = Vec::new();
+        for i in 0..37 {
+            data.extend_from_slice(format!("{},\"x,{}\",\"q\"\"\"\n", i, i * 7).as_bytes());
+        }
+
+        for len in [data.len(), data.len() - 1, 255, 256, 257, 63, 64, 65] {
+            let mut pcsv = ParsedCsv::with_capacity(0);
+            let mut state = ParserState::new();
+            let padded = Padded::from_slice(&data[..len]);
+            find_indexes_resumable(padded.as_padded(), 0, &mut pcsv, &mut state).unwrap();
+            let mut expected = ParsedCsv::with_capacity(0);
+            process_tail_scalar(&data[..len], 0, &mut expected, false);
+            assert_eq!(pcsv.indexes, expected.indexes, "length {}", len);
+        }
+    }
+
+    #[test]
+    fn test_parse_into_reuses_capacity() {
+        let first = Padded::from_slice(b"a,b,c\n1,2,3\n4,5,6\n");
+        let second = Padded::from_slice(b"x,y\n");
+
+        let mut pcsv = parse_csv(first.as_padded()).unwrap();
+        let capacity = pcsv.indexes.capacity();
+
+        parse_into(&mut pcsv, second.as_padded()).unwrap();
+        assert_eq!(pcsv.indexes, vec![1, 3]);
+        assert_eq!(pcsv.indexes.capacity(), capacity);
+
+        parse_into(&mut pcsv, first.as_padded()).unwrap();
+        assert_eq!(pcsv.indexes, parse_csv(first.as_padded()).unwrap().indexes);
+    }
+
+    #[test]
+    fn test_resumable_matches_single_pass() {
+        let mut data = Vec::new();
+        for i in 0..50 {
+            data.extend_from_slice(format!("{},\"a\nb,{}\",c\n", i, i).as_bytes());
+        }
+        let whole = parse_csv(Padded::from_slice(&data).as_padded()).unwrap();
+
+        for split in [1, 30, 64, 100, 333, data.len() - 1] {
+            let mut pcsv = ParsedCsv::with_capacity(0);
+            let mut state = ParserState::new();
+            let head = Padded::from_slice(&data[..split]);
+            find_indexes_resumable(head.as_padded(), 0, &mut pcsv, &mut state).unwrap();
+            let all = Padded::from_slice(&data);
+            find_indexes_resumable(all.as_padded(), split, &mut pcsv, &mut state).unwrap();
+            assert_eq!(pcsv.indexes, whole.indexes, "split at {}", split);
+            assert!(!state.inside_quote());
+        }
+
+        // Counts carry over from one call to the next
+        let mut pcsv = ParsedCsv::with_capacity(0);
+        let mut state = ParserState::with_limits(&Limits {
+            max_records: Some(40),
+            ..Limits::default()
+        });
+        let head = Padded::from_slice(&data[..333]);
+        find_indexes_resumable(head.as_padded(), 0, &mut pcsv, &mut state).unwrap();
+        let all = Padded::from_slice(&data);
+        let err = find_indexes_resumable(all.as_padded(), 333, &mut pcsv, &mut state);
+        assert!(matches!(
+            err,
+            Err(Error::LimitExceeded {
+                limit: Limit::Records,
+                max: 40,
+                ..
+            })
+        ));
+    }
+
+    #[test]
+    fn test_parse_unterminated_quote() {
+        let mut data = b"a,b\n".repeat(30);
+        data.extend_from_slice(b"1,\"open,2\n3,4\n");
+
+        let err = parse_csv(Padded::from_slice(&data).as_padded())
+            .err()
+            .unwrap();
+        assert!(matches!(
+            err,
+            Error::Parse {
+                offset: 122,
+                kind: ParseErrorKind::UnterminatedQuote
+            }
+        ));
+    }
+
+    #[test]
+    fn test_parse_validates_utf8() {
+        let mut data = "name,city\nJosé,Zürich\n".repeat(20).into_bytes();
+        let (pcsv, text) = parse_csv_utf8(
+            Padded::from_slice(&data).as_padded(),
+            &ParseOptions::default(),
+        )
+        .map(|(pcsv, text)| (pcsv, text.to_string()))
+        .unwrap();
+        assert_eq!(text.as_bytes(), &data[..]);
+        assert_eq!(pcsv.indexes.len(), 80);
+
+        data[300] = 0xFF;
+        let options = ParseOptions {
+            validate_utf8: true,
+            ..ParseOptions::default()
+        };
+        let err = parse_csv_with(Padded::from_slice(&data).as_padded(), &options)
+            .err()
+            .unwrap();
+        assert!(matches!(
+            err,
+            Error::Parse {
+                offset: 300,
+                kind: ParseErrorKind::InvalidUtf8
+            }
+        ));
+
+        // Without the option the input is accepted as bytes
+        assert!(parse_csv(Padded::from_slice(&data).as_padded()).is_ok());
+    }
+
+    #[test]
+    fn test_fallback_enforces_limits() {
+        let data = b"a,b,c\n1,2,3\n4,5,6,7\n".repeat(10);
+        let padded = Padded::from_slice(&data);
+        let mut pcsv = ParsedCsv::with_capacity(0);
+        let mut state = ParserState::with_limits(&Limits {
+            max_fields_per_record: Some(3),
+            ..Limits::default()
+        });
+        find_indexes_fallback(
+            padded.as_padded(),
+            0,
+            data.len(),
+            &mut pcsv,
+            &mut state,
+            PASS_LIMITS,
+        );
+        assert!(matches!(
+            state.limits.finish(data.len()),
+            Some(Error::LimitExceeded {
+                limit: Limit::FieldsPerRecord,
+                offset: 17,
+                ..
+            })
+        ));
+    }
+
     #[test]
     fn test_parse_no_separators() {
         let data = vec![b'a'; 100];
-        let pcsv = parse_csv(&data);
+        let pcsv = parse_csv(Padded::from_slice(&data).as_padded()).unwrap();
         assert!(pcsv.indexes.is_empty());
     }
 }
@@ -0,0 +1,435 @@
+//! Column projection
+//!
+//! Instead of flattening every separator into `ParsedCsv::indexes`, a
+//! projection keeps only the spans of the requested columns. Blocks without
+//! a record terminator are skipped with one popcount when none of the
+//! columns they finish is wanted, which is most blocks of a wide file read
+//! for a handful of columns.
+
+use crate::error::{Error, ParseErrorKind, Result};
+use crate::memory::Padded;
+use crate::parser::check_input_len;
+#[cfg(any(target_arch = "x86_64", target_arch = "aarch64"))]
+use crate::parser::{cmp_mask_against_input, fill_input, find_quote_mask};
+use crate::portability::{hamming, leading_zeros, trailing_zeros};
+use crate::record::unquote;
+use std::ops::Range;
+
+/// Marks a column missing from a short record
+const MISSING: u32 = u32::MAX;
+
+/// Columns to keep, by position or by header name
+#[derive(Clone, Debug)]
+pub enum ColumnSelection {
+    /// 0-based column positions; a position may be listed more than once
+    Indices(Vec<usize>),
+    /// Names looked up in the first record, which is then treated as a header
+    Names(Vec<String>),
+}
+
+/// Spans of the selected columns of every record
+#[derive(Clone, Debug, Default)]
+pub struct ProjectedCsv {
+    columns: Vec<usize>,
+    /// Start and end of each selected field, row by row
+    spans: Vec<[u32; 2]>,
+}
+
+impl ProjectedCsv {
+    /// Get the selected column positions, in output order
+    pub fn columns(&self) -> &[usize] {
+        &self.columns
+    }
+
+    /// Get the number of records
+    pub fn len(&self) -> usize {
+        self.spans
+            .len()
+            .checked_div(self.columns.len())
+            .unwrap_or(0)
+    }
+
+    /// Check if there are no records
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Get the byte range of selected column `col` in record `row`
+    ///
+    /// Returns `None` when the record is too short to have the column.
+    pub fn span(&self, row: usize, col: usize) -> Option<Range<usize>> {
+        if col >= self.columns.len() {
+            return None;
+        }
+        let [start, end] = *self.spans.get(row * self.columns.len() + col)?;
+        (start != MISSING).then_some(start as usize..end as usize)
+    }
+
+    /// Get the raw bytes of selected column `col` in record `row` of `buf`
+    pub fn get<'a>(&self, buf: &'a [u8], row: usize, col: usize) -> Option<&'a [u8]> {
+        self.span(row, col).map(|r| &buf[r])
+    }
+}
+
+/// Block-at-a-time projection state
+struct Projector<'a> {
+    data: &'a [u8],
+    /// Selected columns in output order, repeats included
+    columns: Vec<usize>,
+    /// Scan slot of each column up to the last selected one
+    slots: Vec<u32>,
+    /// Smallest selected column at or after each column
+    next_wanted: Vec<usize>,
+    out: ProjectedCsv,
+    col: usize,
+    field_start: usize,
+    /// Whether the record being read is a header to leave out
+    in_header: bool,
+}
+
+impl<'a> Projector<'a> {
+    fn new(data: &'a [u8], columns: Vec<usize>, skip_header: bool) -> Self {
+        let width = columns.iter().max().map_or(0, |&max| max + 1);
+        // A repeated column is scanned once and copied out in `finish`
+        let mut slots = vec![MISSING; width];
+        let mut unique = Vec::with_capacity(columns.len());
+        for &col in &columns {
+            if slots[col] == MISSING {
+                slots[col] = unique.len() as u32;
+                unique.push(col);
+            }
+        }
+        let mut next_wanted = vec![usize::MAX; width + 1];
+        for col in (0..width).rev() {
+            next_wanted[col] = if slots[col] != MISSING {
+                col
+            } else {
+                next_wanted[col + 1]
+            };
+        }
+
+        let mut out = ProjectedCsv {
+            columns: unique,
+            spans: Vec::new(),
+        };
+        if !data.is_empty() && !skip_header {
+            out.spans
+                .extend(std::iter::repeat_n([MISSING; 2], out.columns.len()));
+        }
+        Self {
+            data,
+            columns,
+            slots,
+            next_wanted,
+            out,
+            col: 0,
+            field_start: 0,
+            in_header: skip_header,
+        }
+    }
+
+    #[inline(always)]
+    fn next_wanted(&self, col: usize) -> usize {
+        *self.next_wanted.get(col).unwrap_or(&usize::MAX)
+    }
+
+    /// Store field `self.col` ending at `end` if it is selected
+    #[inline(always)]
+    fn store(&mut self, end: usize) {
+        if self.in_header {
+            return;
+        }
+        if let Some(&slot) = self.slots.get(self.col) {
+            if slot != MISSING {
+                let row = self.out.spans.len() - self.out.columns.len();
+                self.out.spans[row + slot as usize] = [self.field_start as u32, end as u32];
+            }
+        }
+    }
+
+    /// Handle the unquoted separators `seps` of the block at `idx`, of which
+    /// `ends` are record terminators
+    #[inline(always)]
+    fn block(&mut self, idx: usize, mut seps: u64, ends: u64) {
+        let count = hamming(seps) as usize;
+        if ends == 0 && self.next_wanted(self.col) >= self.col + count {
+            // Every field finished in this block is unwanted
+            if count > 0 {
+                self.col += count;
+                self.field_start = idx + 64 - leading_zeros(seps) as usize;
+            }
+            return;
+        }
+
+        while seps != 0 {
+            let bit = trailing_zeros(seps);
+            let pos = idx + bit as usize;
+            if ends & (1 << bit) != 0 {
+                // A CR before the terminator is not part of the last field
+                let end = if pos > self.field_start && self.data[pos - 1] == b'\r' {
+                    pos - 1
+                } else {
+                    pos
+                };
+                self.store(end);
+                self.in_header = false;
+                if pos + 1 < self.data.len() {
+                    let width = self.out.columns.len();
+                    self.out
+                        .spans
+                        .extend(std::iter::repeat_n([MISSING; 2], width));
+                }
+                self.col = 0;
+            } else {
+                self.store(pos);
+                self.col += 1;
+            }
+            self.field_start = pos + 1;
+            seps &= seps - 1;
+        }
+    }
+
+    fn finish(mut self, inside_quote: bool) -> Result<ProjectedCsv> {
+        if inside_quote {
+            return Err(Error::Parse {
+                offset: self.field_start,
+                kind: ParseErrorKind::UnterminatedQuote,
+            });
+        }
+        // Final record without a terminator, possibly ending in an empty field
+        let len = self.data.len();
+        if self.field_start < len || (len > 0 && self.data[len - 1] == b',') {
+            let end = if self.data[len - 1] == b'\r' {
+                len - 1
+            } else {
+                len
+            };
+            self.store(end);
+        }
+        if self.columns.len() != self.out.columns.len() {
+            let slots: Vec<usize> = self
+                .columns
+                .iter()
+                .map(|&col| self.slots[col] as usize)
+                .collect();
+            let spans = self
+                .out
+                .spans
+                .chunks_exact(self.out.columns.len())
+                .flat_map(|row| slots.iter().map(|&slot| row[slot]))
+                .collect();
+            self.out = ProjectedCsv {
+                columns: self.columns,
+                spans,
+            };
+        }
+        Ok(self.out)
+    }
+}
+
+/// Parse `buf`, keeping only the spans of the selected columns
+///
+/// With `has_header` the first record is left out of the result. Selecting
+/// by name always treats the first record as a header, and fails with
+/// `Error::UnknownColumn` if a name is not in it.
+pub fn project_csv(
+    buf: Padded<&[u8]>,
+    selection: &ColumnSelection,
+    has_header: bool,
+) -> Result<ProjectedCsv> {
+    check_input_len(buf.len())?;
+    let (columns, has_header) = match selection {
+        ColumnSelection::Indices(columns) => (columns.clone(), has_header),
+        ColumnSelection::Names(names) => (resolve_names(buf.data(), names)?, true),
+    };
+    let mut projector = Projector::new(buf.data(), columns, has_header);
+    let inside_quote = scan(buf, &mut projector);
+    projector.finish(inside_quote)
+}
+
+/// Find the position of each name in the first record of `data`
+fn resolve_names(data: &[u8], names: &[String]) -> Result<Vec<usize>> {
+    let mut header = Vec::new();
+    let mut in_quote = false;
+    let mut start = 0;
+    for (i, &byte) in data.iter().enumerate() {
+        match byte {
+            b'"' => in_quote = !in_quote,
+            b',' if !in_quote => {
+                header.push(&data[start..i]);
+                start = i + 1;
+            }
+            b'\n' if !in_quote => break,
+            _ => {}
+        }
+    }
+    let end = data[start..]
+        .iter()
+        .position(|&b| b == b'\n')
+        .map_or(data.len(), |n| start + n);
+    header.push(
+        data[start..end]
+            .strip_suffix(b"\r")
+            .unwrap_or(&data[start..end]),
+    );
+
+    names
+        .iter()
+        .map(|name| {
+            header
+                .iter()
+                .position(|field| *unquote(field) == *name.as_bytes())
+                .ok_or_else(|| Error::UnknownColumn { name: name.clone() })
+        })
+        .collect()
+}
+
+/// Run the projector over every block, returning the final quote state
+#[cfg(target_arch = "x86_64")]
+fn scan(buf: Padded<&[u8]>, projector: &mut Projector<'_>) -> bool {
+    if is_x86_feature_detected!("avx2") && is_x86_feature_detected!("pclmulqdq") {
+        unsafe { scan_avx2(buf, projector) }
+    } else {
+        scan_scalar(buf.data(), projector)
+    }
+}
+
+#[cfg(target_arch = "aarch64")]
+fn scan(buf: Padded<&[u8]>, projector: &mut Projector<'_>) -> bool {
+    unsafe { scan_blocks(buf, projector) }
+}
+
+#[cfg(not(any(target_arch = "x86_64", target_arch = "aarch64")))]
+fn scan(buf: Padded<&[u8]>, projector: &mut Projector<'_>) -> bool {
+    scan_scalar(buf.data(), projector)
+}
+
+#[cfg(target_arch = "x86_64")]
+#[target_feature(enable = "avx2")]
+#[target_feature(enable = "pclmulqdq")]
+unsafe fn scan_avx2(buf: Padded<&[u8]>, projector: &mut Projector<'_>) -> bool {
+    scan_blocks(buf, projector)
+}
+
+/// Scan 64-byte blocks; the last one reads into the zeroed padding
+#[cfg(any(target_arch = "x86_64", target_arch = "aarch64"))]
+#[inline(always)]
+unsafe fn scan_blocks(buf: Padded<&[u8]>, projector: &mut Projector<'_>) -> bool {
+    let len = buf.len();
+    let ptr = buf.with_padding().as_ptr();
+    let mut prev_iter_inside_quote = 0u64;
+    let mut idx = 0;
+
+    while idx < len {
+        let input = fill_input(ptr.add(idx));
+        let quote_mask = find_quote_mask(input, &mut prev_iter_inside_quote);
+        let sep = cmp_mask_against_input(input, b',');
+        let end = cmp_mask_against_input(input, b'\n');
+
+        projector.block(idx, (sep | end) & !quote_mask, end & !quote_mask);
+        idx += 64;
+    }
+    prev_iter_inside_quote != 0
+}
+
+/// Build the block masks with scalar code
+#[cfg_attr(target_arch = "aarch64", allow(dead_code))]
+fn scan_scalar(data: &[u8], projector: &mut Projector<'_>) -> bool {
+    let mut in_quote = false;
+    for (n, block) in data.chunks(64).enumerate() {
+        let (mut seps, mut ends) = (0u64, 0u64);
+        for (i, &byte) in block.iter().enumerate() {
+            match byte {
+                b'"' => in_quote = !in_quote,
+                b',' if !in_quote => seps |= 1 << i,
+                b'\n' if !in_quote => {
+                    seps |= 1 << i;
+                    ends |= 1 << i;
+                }
+                _ => {}
+            }
+        }
+        projector.block(64 * n, seps, ends);
+    }
+    in_quote
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::parse_csv;
+
+    fn wide_csv(rows: usize, cols: usize) -> Vec<u8> {
+        let mut data = Vec::new();
+        for r in 0..rows {
+            let fields: Vec<String> = (0..cols)
+                .map(|c| match (r + c) % 7 {
+                    0 => format!("\"q,{}\"", c),
+                    _ => format!("r{}c{}", r, c),
+                })
+                .collect();
+            data.extend_from_slice(fields.join(",").as_bytes());
+            data.extend_from_slice(if r % 2 == 0 { b"\r\n" } else { b"\n" });
+        }
+        data
+    }
+
+    #[test]
+    fn test_projection_matches_records() {
+        let data = wide_csv(40, 95);
+        let padded = Padded::from_slice(&data);
+        let columns = vec![93, 0, 17, 50, 0, 93];
+        let projected = project_csv(
+            padded.as_padded(),
+            &ColumnSelection::Indices(columns.clone()),
+            false,
+        )
+        .unwrap();
+        let pcsv = parse_csv(padded.as_padded()).unwrap();
+
+        assert_eq!(projected.len(), 40);
+        for (row, record) in pcsv.records(&data).enumerate() {
+            for (slot, &col) in columns.iter().enumerate() {
+                assert_eq!(projected.get(&data, row, slot), record.get(col));
+            }
+        }
+
+        let mut scalar = Projector::new(&data, columns, false);
+        let in_quote = scan_scalar(&data, &mut scalar);
+        assert_eq!(scalar.finish(in_quote).unwrap().spans, projected.spans);
+    }
+
+    #[test]
+    fn test_projection_by_name() {
+        let data = b"id,\"full name\",age\n1,Ann,30\n2,Bob\n3,Cy,";
+        let padded = Padded::from_slice(data);
+        let names = vec!["age".to_string(), "full name".to_string()];
+        let projected =
+            project_csv(padded.as_padded(), &ColumnSelection::Names(names), false).unwrap();
+
+        assert_eq!(projected.columns(), [2, 1]);
+        assert_eq!(projected.len(), 3);
+        assert_eq!(projected.get(data, 0, 0), Some(&b"30"[..]));
+        assert_eq!(projected.get(data, 1, 0), None);
+        assert_eq!(projected.get(data, 1, 1), Some(&b"Bob"[..]));
+        assert_eq!(projected.get(data, 2, 0), Some(&b""[..]));
+
+        let names = vec!["age".to_string(), "id".to_string(), "age".to_string()];
+        let repeated =
+            project_csv(padded.as_padded(), &ColumnSelection::Names(names), false).unwrap();
+        assert_eq!(repeated.columns(), [2, 0, 2]);
+        assert_eq!(repeated.get(data, 0, 0), Some(&b"30"[..]));
+        assert_eq!(repeated.get(data, 0, 2), Some(&b"30"[..]));
+        assert_eq!(repeated.get(data, 1, 1), Some(&b"2"[..]));
+        assert_eq!(repeated.get(data, 1, 2), None);
+
+        let err = project_csv(
+            padded.as_padded(),
+            &ColumnSelection::Names(vec!["zip".to_string()]),
+            false,
+        )
+        .err()
+        .unwrap();
+        assert!(matches!(err, Error::UnknownColumn { ref name } if name == "zip"));
+    }
+}
@@ -0,0 +1,186 @@
+//! Field-count checks across records
+//!
+//! Every record is expected to have as many fields as the first one, which
+//! is usually the header. Counts come from the separator indexes the scan
+//! already produced: a record ends at the first index that points at `\n`.
+
+use crate::error::{Error, ParseErrorKind, Result};
+use crate::parser::ParsedCsv;
+
+/// What to do with records whose field count differs from the first record
+///
+/// A blank line, the last one included, is a record of one empty field, so
+/// in a file of wider records it is ragged like any other short record.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum RaggedMode {
+    /// Do not check field counts
+    #[default]
+    Allow,
+    /// Fail the parse at the first ragged record
+    Error,
+    /// Leave ragged records out of `ParsedCsv::records`
+    Skip,
+    /// Record ragged records in `ParsedCsv::ragged_rows` and keep them
+    Report,
+}
+
+/// A record whose field count differs from the first record
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct RaggedRow {
+    /// 1-based line the record starts on, counting line feeds inside
+    /// quoted fields
+    pub line: usize,
+    /// Byte offset of the start of the record
+    pub offset: usize,
+    /// Field count of the first record
+    pub expected: usize,
+    /// Field count of this record
+    pub actual: usize,
+}
+
+/// Check the field count of every record whose indexes start at `first`
+pub(crate) fn check_field_counts(
+    buf: &[u8],
+    pcsv: &mut ParsedCsv,
+    first: usize,
+    mode: RaggedMode,
+) -> Result<()> {
+    if mode == RaggedMode::Allow {
+        return Ok(());
+    }
+
+    let mut ragged = Vec::new();
+    let mut expected = None;
+    let mut line = 1;
+    let mut start = 0;
+    let mut fields = 1;
+
+    let mut check = |line: usize, start: usize, actual: usize| -> Result<()> {
+        let expected = *expected.get_or_insert(actual);
+        if actual == expected {
+            return Ok(());
+        }
+        if mode == RaggedMode::Error {
+            return Err(Error::Parse {
+                offset: start,
+                kind: ParseErrorKind::FieldCount {
+                    line,
+                    expected,
+                    actual,
+                },
+            });
+        }
+        ragged.push(RaggedRow {
+            line,
+            offset: start,
+            expected,
+            actual,
+        });
+        Ok(())
+    };
+
+    for &idx in &pcsv.indexes[first..] {
+        let idx = idx as usize;
+        if buf[idx] == b'\n' {
+            check(line, start, fields)?;
+            // Quoted fields may hold line feeds of their own
+            line += 1 + buf[start..idx].iter().filter(|&&b| b == b'\n').count();
+            start = idx + 1;
+            fields = 1;
+        } else {
+            fields += 1;
+        }
+    }
+    // Final record without a terminator
+    if start < buf.len() || fields > 1 {
+        check(line, start, fields)?;
+    }
+
+    pcsv.ragged_rows = ragged;
+    pcsv.skip_ragged = mode == RaggedMode::Skip;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::memory::Padded;
+    use crate::parser::{parse_csv_with, ParseOptions};
+
+    fn parse(data: &[u8], ragged: RaggedMode) -> Result<ParsedCsv> {
+        let options = ParseOptions {
+            ragged,
+            ..ParseOptions::default()
+        };
+        parse_csv_with(Padded::from_slice(data).as_padded(), &options)
+    }
+
+    const DATA: &[u8] = b"a,b,c\n1,2,3\n4,5,6,7\n\"x\ny\",8,9\n10,11\n";
+
+    #[test]
+    fn test_ragged_report() {
+        let pcsv = parse(DATA, RaggedMode::Report).unwrap();
+        assert_eq!(
+            pcsv.ragged_rows,
+            [
+                RaggedRow {
+                    line: 3,
+                    offset: 12,
+                    expected: 3,
+                    actual: 4
+                },
+                RaggedRow {
+                    line: 6,
+                    offset: 30,
+                    expected: 3,
+                    actual: 2
+                },
+            ]
+        );
+        assert_eq!(pcsv.records(DATA).count(), 5);
+
+        // A blank last line is a short record too
+        let pcsv = parse(b"a,b\n1,2\n\n", RaggedMode::Report).unwrap();
+        assert_eq!(
+            pcsv.ragged_rows,
+            [RaggedRow {
+                line: 3,
+                offset: 8,
+                expected: 2,
+                actual: 1
+            }]
+        );
+    }
+
+    #[test]
+    fn test_ragged_skip() {
+        let pcsv = parse(DATA, RaggedMode::Skip).unwrap();
+        let first: Vec<&[u8]> = pcsv.records(DATA).map(|r| r.get(0).unwrap()).collect();
+        assert_eq!(first, vec![&b"a"[..], b"1", b"\"x\ny\""]);
+    }
+
+    #[test]
+    fn test_ragged_error() {
+        let err = parse(DATA, RaggedMode::Error).err().unwrap();
+        assert!(matches!(
+            err,
+            Error::Parse {
+                offset: 12,
+                kind: ParseErrorKind::FieldCount {
+                    line: 3,
+                    expected: 3,
+                    actual: 4
+                }
+            }
+        ));
+        assert_eq!(
+            err.to_string(),
+            "line 3 has 4 fields, expected 3 at byte 12"
+        );
+
+        // An unterminated last record is checked too
+        assert!(parse(b"a,b\n1,2\n3", RaggedMode::Error).is_err());
+        assert!(parse(b"a,b\n1,2\n3,4", RaggedMode::Error).is_ok());
+        assert!(parse(b"", RaggedMode::Error).is_ok());
+    }
+}
@@ -0,0 +1,321 @@
+//! Record-level views over parsed separator indexes
+
+use crate::parser::ParsedCsv;
+use crate::ragged::RaggedRow;
+use std::borrow::Cow;
+use std::ops::Range;
+
+/// A single CSV record borrowed from the parsed buffer
+///
+/// Fields are returned raw: surrounding quotes and doubled quotes are kept
+/// as they appear in the input. A `\r` before the record terminator is not
+/// part of the last field.
+#[derive(Clone, Copy, Debug)]
+pub struct Record<'a> {
+    buf: &'a [u8],
+    start: usize,
+    end: usize,
+    seps: &'a [u32],
+    /// Whether `buf` is known to be UTF-8
+    utf8: bool,
+}
+
+impl<'a> Record<'a> {
+    /// Get the number of fields in the record
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.seps.len() + 1
+    }
+
+    /// Check if the record has no fields (never true: an empty line holds one empty field)
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        false
+    }
+
+    /// Get the byte range of field `i` in the buffer
+    #[inline]
+    pub fn field_range(&self, i: usize) -> Option<Range<usize>> {
+        if i > self.seps.len() {
+            return None;
+        }
+        let start = if i == 0 {
+            self.start
+        } else {
+            self.seps[i - 1] as usize + 1
+        };
+        let end = match self.seps.get(i) {
+            Some(&sep) => sep as usize,
+            None => self.end,
+        };
+        Some(start..end)
+    }
+
+    /// Get the raw bytes of field `i`
+    #[inline]
+    pub fn get(&self, i: usize) -> Option<&'a [u8]> {
+        self.field_range(i).map(|r| &self.buf[r])
+    }
+
+    /// Get field `i` as a string slice
+    ///
+    /// For records from `ParsedCsv::str_records` only the ends of the field
+    /// are checked to be character boundaries; otherwise the field is
+    /// validated. `None` is returned if it is not UTF-8.
+    #[inline]
+    pub fn get_str(&self, i: usize) -> Option<&'a str> {
+        let range = self.field_range(i)?;
+        if self.utf8 {
+            // SAFETY: `buf` came from a `&str`. The indexes may belong to
+            // another buffer, so `str::get` still checks both ends.
+            let text = unsafe { std::str::from_utf8_unchecked(self.buf) };
+            text.get(range)
+        } else {
+            std::str::from_utf8(&self.buf[range]).ok()
+        }
+    }
+
+    /// Get field `i` with its surrounding quotes removed and doubled quotes
+    /// collapsed
+    #[inline]
+    pub fn get_unquoted(&self, i: usize) -> Option<Cow<'a, [u8]>> {
+        self.get(i).map(unquote)
+    }
+
+    /// Iterate over the raw bytes of every field
+    pub fn iter(&self) -> impl Iterator<Item = &'a [u8]> + 'a {
+        let record = *self;
+        (0..record.len()).map(move |i| &record.buf[record.field_range(i).unwrap()])
+    }
+
+    /// Get the byte range of the whole record, excluding its terminator
+    #[inline]
+    pub fn range(&self) -> Range<usize> {
+        self.start..self.end
+    }
+}
+
+/// Remove the quotes around a raw field and collapse doubled quotes
+///
+/// Unquoted fields are returned as is without copying.
+pub fn unquote(field: &[u8]) -> Cow<'_, [u8]> {
+    let inner = match field {
+        [b'"', inner @ .., b'"'] => inner,
+        _ => return Cow::Borrowed(field),
+    };
+    if !inner.contains(&b'"') {
+        return Cow::Borrowed(inner);
+    }
+
+    let mut out = Vec::with_capacity(inner.len());
+    let mut bytes = inner.iter();
+    while let Some(&b) = bytes.next() {
+        out.push(b);
+        if b == b'"' {
+            // Skip the second quote of a pair
+            bytes.next();
+        }
+    }
+    Cow::Owned(out)
+}
+
+/// Iterator over the records of a parsed buffer
+///
+/// Ragged records are left out when the input was parsed with
+/// `RaggedMode::Skip`.
+pub struct Records<'a> {
+    buf: &'a [u8],
+    indexes: &'a [u32],
+    pos: usize,
+    start: usize,
+    utf8: bool,
+    /// Ragged records still to be left out, in input order
+    skip: &'a [RaggedRow],
+    /// The header, once it has been read past
+    pub(crate) header: Option<Record<'a>>,
+}
+
+impl<'a> Records<'a> {
+    pub(crate) fn new(pcsv: &'a ParsedCsv, buf: &'a [u8]) -> Self {
+        Self {
+            buf,
+            indexes: &pcsv.indexes,
+            pos: 0,
+            start: 0,
+            utf8: false,
+            skip: if pcsv.skip_ragged {
+                &pcsv.ragged_rows
+            } else {
+                &[]
+            },
+            header: None,
+        }
+    }
+
+    /// Iterate over the records after the header, if the input has one
+    fn after_header(pcsv: &'a ParsedCsv, buf: &'a [u8], utf8: bool) -> Self {
+        let mut records = Records {
+            utf8,
+            ..Records::new(pcsv, buf)
+        };
+        if pcsv.has_header {
+            records.header = records.next_record();
+        }
+        records
+    }
+
+    /// Cut the next record, skipped or not
+    fn next_record(&mut self) -> Option<Record<'a>> {
+        let rest = &self.indexes[self.pos..];
+        let terminator = rest.iter().position(|&idx| self.buf[idx as usize] == b'\n');
+
+        let (seps, mut end, next_start) = match terminator {
+            Some(n) => {
+                let end = rest[n] as usize;
+                self.pos += n + 1;
+                (&rest[..n], end, end + 1)
+            }
+            None => {
+                // Final record without a terminator
+                if self.start >= self.buf.len() && rest.is_empty() {
+                    return None;
+                }
+                self.pos = self.indexes.len();
+                (rest, self.buf.len(), self.buf.len())
+            }
+        };
+
+        let last_field_start = seps.last().map_or(self.start, |&s| s as usize + 1);
+        if end > last_field_start && self.buf[end - 1] == b'\r' {
+            end -= 1;
+        }
+
+        let record = Record {
+            buf: self.buf,
+            start: self.start,
+            end,
+            seps,
+            utf8: self.utf8,
+        };
+        self.start = next_start;
+        Some(record)
+    }
+}
+
+impl<'a> Iterator for Records<'a> {
+    type Item = Record<'a>;
+
+    fn next(&mut self) -> Option<Record<'a>> {
+        loop {
+            let record = self.next_record()?;
+            match self.skip.split_first() {
+                Some((row, rest)) if row.offset == record.start => self.skip = rest,
+                _ => return Some(record),
+            }
+        }
+    }
+}
+
+impl ParsedCsv {
+    /// Iterate over the records of `buf`, which must be the buffer these
+    /// indexes were parsed from
+    ///
+    /// The header is not included when the input was parsed with
+    /// `ParseOptions::has_header`.
+    pub fn records<'a>(&'a self, buf: &'a [u8]) -> Records<'a> {
+        Records::after_header(self, buf, false)
+    }
+
+    /// Iterate over the records of validated text, such as the string
+    /// returned by `parse_csv_utf8`
+    ///
+    /// `Record::get_str` returns `None` for a field that does not start and
+    /// end on a character boundary of `buf`.
+    pub fn str_records<'a>(&'a self, buf: &'a str) -> Records<'a> {
+        Records::after_header(self, buf.as_bytes(), true)
+    }
+
+    /// Get the header record of `buf`, if the input was parsed with
+    /// `ParseOptions::has_header`
+    pub fn headers<'a>(&'a self, buf: &'a [u8]) -> Option<Record<'a>> {
+        if !self.has_header {
+            return None;
+        }
+        Records::new(self, buf).next_record()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::memory::Padded;
+    use crate::parser::{parse_csv, parse_csv_utf8, ParseOptions};
+
+    #[test]
+    fn test_records() {
+        let data = Padded::from_slice(b"a,b,c\n1,\"2,x\",3\r\n,,\nlast,row");
+        let pcsv = parse_csv(data.as_padded()).unwrap();
+        let records: Vec<Vec<&[u8]>> = pcsv
+            .records(data.data())
+            .map(|r| r.iter().collect())
+            .collect();
+
+        assert_eq!(
+            records,
+            vec![
+                vec![&b"a"[..], b"b", b"c"],
+                vec![&b"1"[..], b"\"2,x\"", b"3"],
+                vec![&b""[..], b"", b""],
+                vec![&b"last"[..], b"row"],
+            ]
+        );
+    }
+
+    #[test]
+    fn test_record_ranges() {
+        let data = Padded::from_slice(b"ab,c\nd\n");
+        let pcsv = parse_csv(data.as_padded()).unwrap();
+        let records: Vec<_> = pcsv.records(data.data()).collect();
+
+        assert_eq!(records.len(), 2);
+        assert_eq!(records[0].range(), 0..4);
+        assert_eq!(records[0].field_range(1), Some(3..4));
+        assert_eq!(records[0].get(2), None);
+        assert_eq!(records[1].get(0), Some(&b"d"[..]));
+    }
+
+    #[test]
+    fn test_str_records() {
+        let data = Padded::from_slice("id,name\n1,Zoë\n".as_bytes());
+        let (pcsv, text) = parse_csv_utf8(data.as_padded(), &ParseOptions::default()).unwrap();
+        let names: Vec<&str> = pcsv
+            .str_records(text)
+            .map(|r| r.get_str(1).unwrap())
+            .collect();
+        assert_eq!(names, vec!["name", "Zoë"]);
+
+        let data = Padded::from_slice(b"a,\xFF\n");
+        let pcsv = parse_csv(data.as_padded()).unwrap();
+        let record = pcsv.records(data.data()).next().unwrap();
+        assert_eq!(record.get_str(0), Some("a"));
+        assert_eq!(record.get_str(1), None);
+
+        // Indexes from another buffer must not split a character
+        let data = Padded::from_slice(b"a,bc\n");
+        let pcsv = parse_csv(data.as_padded()).unwrap();
+        let record = pcsv.str_records("ébc\n").next().unwrap();
+        assert_eq!(record.get_str(0), None);
+        assert_eq!(record.get_str(1), Some("bc"));
+    }
+
+    #[test]
+    fn test_unquote() {
+        use super::unquote;
+        use std::borrow::Cow;
+
+        assert!(matches!(unquote(b"plain"), Cow::Borrowed(b"plain")));
+        assert!(matches!(unquote(b"\"a,b\""), Cow::Borrowed(b"a,b")));
+        assert_eq!(unquote(b"\"say \"\"hi\"\"\""), &b"say \"hi\""[..]);
+        assert_eq!(unquote(b"\"\""), &b""[..]);
+        assert_eq!(unquote(b"\""), &b"\""[..]);
+    }
+}
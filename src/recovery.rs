@@ -0,0 +1,340 @@
+//! Recovery from runaway quoted fields
+//!
+//! A stray quote flips the carried quote state, so from that point on the
+//! scan treats data as quoted and quoted fields as data, merging records
+//! until another stray quote flips it back. Recovery looks for records that
+//! swallowed line feeds and are implausible, cuts them at their first line
+//! feed, and rescans from there with a fresh quote state. The rescan only
+//! runs ahead of the records being checked, so each cut throws away little
+//! work and many stray quotes still cost linear time.
+
+use crate::error::{Error, Limit, ParseErrorKind, Result};
+use crate::memory::Padded;
+use crate::parser::{scan_checked, ParsedCsv, ParserState};
+
+/// Limits that decide when a quoted field has run away
+#[derive(Clone, Debug)]
+pub struct RecoveryOptions {
+    /// Longest record, in bytes, that may contain a quoted line feed
+    pub max_quoted_len: usize,
+    /// Most line feeds a record may contain inside quoted fields
+    pub max_quoted_lines: usize,
+}
+
+impl Default for RecoveryOptions {
+    fn default() -> Self {
+        Self {
+            max_quoted_len: 64 * 1024,
+            max_quoted_lines: 8,
+        }
+    }
+}
+
+/// A problem the parser recovered from
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct RecordError {
+    /// Byte offset where the problem starts
+    pub offset: usize,
+    pub kind: ParseErrorKind,
+}
+
+/// Resynchronize every runaway record in the indexes from `first` on
+///
+/// A record is runaway when it contains a quoted line feed and either
+/// exceeds the limits in `options` or has a different field count than the
+/// first good record. A quote still open at the end of the input is always
+/// runaway, and so is a record with a quoted line feed in which a field is
+/// longer than `max_field_len`. Each runaway record ends at its first line
+/// feed, where the input is rescanned with the limits in `state`; the
+/// errors are collected in `pcsv.errors`.
+///
+/// Field lengths are checked here rather than by the scan, since a runaway
+/// quote makes one long field of everything up to the next quote. A field
+/// still too long once its record is cut fails the parse.
+pub(crate) fn recover(
+    buf: Padded<&[u8]>,
+    pcsv: &mut ParsedCsv,
+    first: usize,
+    state: &mut ParserState,
+    options: &RecoveryOptions,
+    max_field_len: Option<usize>,
+) -> Result<()> {
+    let data = buf.data();
+    let max_field_len = max_field_len.unwrap_or(usize::MAX);
+    let check_field = |(offset, len): (usize, usize)| {
+        if len > max_field_len {
+            return Err(Error::LimitExceeded {
+                limit: Limit::FieldLength,
+                max: max_field_len,
+                offset,
+            });
+        }
+        Ok(())
+    };
+    let mut expected = None;
+    let mut pos = first;
+    let mut start = 0;
+    // Complete records before `start`, for the record limit
+    let mut records = 0;
+    // Indexes are valid up to `scanned`; after a cut the input is rescanned
+    // from `resync` on only as far as the records being checked need
+    let mut scanned = data.len();
+    let mut resync = 0;
+
+    loop {
+        let rest = &pcsv.indexes[pos..];
+        let terminator = rest.iter().position(|&idx| data[idx as usize] == b'\n');
+        let (fields, end) = match terminator {
+            Some(n) => (n + 1, rest[n] as usize),
+            None if start >= data.len() && rest.is_empty() => break,
+            None => (rest.len() + 1, scanned),
+        };
+        let last = terminator.is_none();
+
+        let record = &data[start..end];
+        let embedded = record.iter().filter(|&&b| b == b'\n').count();
+        let too_long = embedded > options.max_quoted_lines || record.len() > options.max_quoted_len;
+        let widest = longest_field(start, &rest[..fields - 1], end);
+        let too_wide = widest.1 > max_field_len;
+        if last && scanned < data.len() && !(embedded > 0 && (too_long || too_wide)) {
+            // Without a line feed there is nowhere to cut the field short
+            check_field(widest)?;
+            // Double the rescanned stretch, so that the bytes thrown away
+            // at the next cut are at most those kept since the last one
+            let window = (scanned - resync).max(64).next_multiple_of(64);
+            let stop = (scanned + window).min(data.len());
+            scan_checked(buf, scanned, stop, pcsv, state, 0)?;
+            scanned = stop;
+            continue;
+        }
+        let runaway = (last && state.inside_quote())
+            || (embedded > 0
+                && (too_long || too_wide || expected.is_some_and(|expected| expected != fields)));
+
+        if runaway {
+            // Fields before the first line feed are kept; the one holding
+            // it opened the runaway quote
+            let cut = record
+                .iter()
+                .position(|&b| b == b'\n')
+                .map_or(end, |i| start + i);
+            let seps = &pcsv.indexes[pos..pos + fields - 1];
+            let kept = seps.partition_point(|&sep| (sep as usize) < cut);
+            check_field(longest_field(start, &seps[..kept], cut))?;
+            let field_start = if kept > 0 {
+                seps[kept - 1] as usize + 1
+            } else {
+                start
+            };
+            let quote = data[field_start..cut]
+                .iter()
+                .position(|&b| b == b'"')
+                .map_or(field_start, |i| field_start + i);
+            pcsv.errors.push(RecordError {
+                offset: quote,
+                kind: ParseErrorKind::UnterminatedQuote,
+            });
+
+            if cut == end {
+                // No line feed to resynchronize at
+                break;
+            }
+            pcsv.indexes.truncate(pos + kept);
+            pcsv.indexes.push(cut as u32);
+            records += 1;
+            state.reset_quote();
+            state.limits.resume(records, start, cut + 1)?;
+            scanned = cut + 1;
+            resync = cut + 1;
+
+            expected.get_or_insert(kept + 1);
+            pos += kept + 1;
+            start = cut + 1;
+            continue;
+        }
+
+        check_field(widest)?;
+        expected.get_or_insert(fields);
+        if last {
+            break;
+        }
+        records += 1;
+        pos += fields;
+        start = end + 1;
+    }
+    Ok(())
+}
+
+/// Offset and length of the longest field of the record from `start` to
+/// `end` with separators `seps`
+fn longest_field(start: usize, seps: &[u32], end: usize) -> (usize, usize) {
+    let mut longest = (start, 0);
+    let mut field_start = start;
+    for pos in seps.iter().map(|&sep| sep as usize).chain([end]) {
+        if pos - field_start > longest.1 {
+            longest = (field_start, pos - field_start);
+        }
+        field_start = pos + 1;
+    }
+    longest
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::limits::Limits;
+    use crate::parser::{parse_csv_with, ParseOptions};
+
+    fn parse(data: &[u8]) -> ParsedCsv {
+        let options = ParseOptions {
+            recovery: Some(RecoveryOptions::default()),
+            ..ParseOptions::default()
+        };
+        parse_csv_with(Padded::from_slice(data).as_padded(), &options).unwrap()
+    }
+
+    fn first_fields(pcsv: &ParsedCsv, data: &[u8]) -> Vec<Vec<u8>> {
+        pcsv.records(data)
+            .map(|r| r.get(0).unwrap().to_vec())
+            .collect()
+    }
+
+    #[test]
+    fn test_recover_stray_quote() {
+        let mut data = b"id,name,note\n1,\"broken,x\n".to_vec();
+        for i in 2..200 {
+            data.extend_from_slice(format!("{},\"n,{}\",ok\n", i, i).as_bytes());
+        }
+        let pcsv = parse(&data);
+
+        assert_eq!(
+            pcsv.errors,
+            [RecordError {
+                offset: 15,
+                kind: ParseErrorKind::UnterminatedQuote
+            }]
+        );
+        let ids = first_fields(&pcsv, &data);
+        assert_eq!(ids.len(), 200);
+        assert_eq!(ids[1], b"1");
+        assert_eq!(ids[199], b"199");
+        let record = pcsv.records(&data).nth(1).unwrap();
+        assert_eq!(record.get(1), Some(&b"\"broken,x"[..]));
+    }
+
+    #[test]
+    fn test_recover_keeps_multiline_fields() {
+        let data = b"a,b\n1,\"two\nlines\"\n2,x\n";
+        let pcsv = parse(data);
+        assert!(pcsv.errors.is_empty());
+        assert_eq!(first_fields(&pcsv, data), [b"a", b"1", b"2"]);
+    }
+
+    #[test]
+    fn test_recover_unterminated_last_record() {
+        let data = b"a,b\n1,\"x";
+        let pcsv = parse(data);
+        assert_eq!(pcsv.errors.len(), 1);
+        assert_eq!(pcsv.errors[0].offset, 6);
+
+        // Without recovery the open quote is an error
+        let err = parse_csv_with(
+            Padded::from_slice(data).as_padded(),
+            &ParseOptions::default(),
+        );
+        assert!(matches!(err, Err(Error::Parse { offset: 6, .. })));
+    }
+
+    #[test]
+    fn test_recover_many_stray_quotes() {
+        // Each stray quote swallows more line feeds than allowed; every cut
+        // rescans only a little past itself
+        let mut data = b"id,name\n".to_vec();
+        for i in 1..5000 {
+            let row = if i % 10 == 0 {
+                format!("{},\"n{}\n", i, i)
+            } else {
+                format!("{},n{}\n", i, i)
+            };
+            data.extend_from_slice(row.as_bytes());
+        }
+        let pcsv = parse(&data);
+
+        assert_eq!(pcsv.errors.len(), 499);
+        assert_eq!(pcsv.errors[0].offset, 56);
+        let ids = first_fields(&pcsv, &data);
+        assert_eq!(ids.len(), 5000);
+        for (i, id) in ids.iter().enumerate().skip(1) {
+            assert_eq!(id, i.to_string().as_bytes());
+        }
+    }
+
+    #[test]
+    fn test_recover_within_limits() {
+        // The stray quote hides the records after it from the first scan
+        let mut data = b"id,name\n1,\"broken\n".to_vec();
+        for i in 2..2000 {
+            data.extend_from_slice(format!("{},n{}\n", i, i).as_bytes());
+        }
+        let parse = |data: &[u8], limits| {
+            let options = ParseOptions {
+                recovery: Some(RecoveryOptions::default()),
+                limits,
+                ..ParseOptions::default()
+            };
+            parse_csv_with(Padded::from_slice(data).as_padded(), &options).map(|pcsv| pcsv.errors)
+        };
+
+        let err = parse(
+            &data,
+            Limits {
+                max_records: Some(10),
+                ..Limits::default()
+            },
+        );
+        assert!(matches!(
+            err,
+            Err(Error::LimitExceeded {
+                limit: Limit::Records,
+                max: 10,
+                ..
+            })
+        ));
+        let errors = parse(
+            &data,
+            Limits {
+                max_records: Some(2000),
+                ..Limits::default()
+            },
+        );
+        assert_eq!(errors.unwrap().len(), 1);
+
+        // The runaway quote reads as one long field until it is cut
+        let field_limit = Limits {
+            max_field_len: Some(1000),
+            ..Limits::default()
+        };
+        let errors = parse(&data, field_limit.clone()).unwrap();
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].offset, 10);
+
+        // A field too long on its own still fails, cut or not
+        let long = "x".repeat(1500);
+        for row in [format!("1,{}\n", long), format!("1,\"{}\n2\n", long)] {
+            let data = format!("id,name\n{}3,y\n", row);
+            let err = parse(data.as_bytes(), field_limit.clone());
+            assert!(
+                matches!(
+                    err,
+                    Err(Error::LimitExceeded {
+                        limit: Limit::FieldLength,
+                        max: 1000,
+                        offset: 10
+                    })
+                ),
+                "{:?}",
+                err
+            );
+        }
+    }
+}
@@ -0,0 +1,353 @@
+//! Column type inference over sampled records
+//!
+//! Every field of a column is tried against each type that all earlier
+//! fields matched; a column ends up with the narrowest type left, in the
+//! order null, boolean, integer, float, date, timestamp and string.
+
+use crate::conventions::{Classifier, ValueConventions};
+use crate::datetime::{iso_kernel, parse_date_with, parse_timestamp_with, IsoKernel};
+use crate::float::parse_f64;
+use crate::header::HeaderNormalization;
+use crate::numeric::{digits_kernel, parse_i64_with, DigitsKernel};
+use crate::parser::ParsedCsv;
+use std::fmt::{self, Write};
+
+/// Example values kept per column
+const MAX_EXAMPLES: usize = 3;
+
+/// The type inferred for a column
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DataType {
+    /// Every sampled field is null
+    Null,
+    /// True and false tokens
+    Boolean,
+    /// Integers that fit `i64`
+    Int64,
+    /// Numbers that `float::parse_f64` accepts
+    Float64,
+    /// ISO 8601 dates
+    Date,
+    /// ISO 8601 timestamps
+    Timestamp,
+    /// Anything else
+    Utf8,
+}
+
+impl fmt::Display for DataType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            DataType::Null => "null",
+            DataType::Boolean => "boolean",
+            DataType::Int64 => "int64",
+            DataType::Float64 => "float64",
+            DataType::Date => "date",
+            DataType::Timestamp => "timestamp",
+            DataType::Utf8 => "string",
+        };
+        f.write_str(name)
+    }
+}
+
+/// One column of a `Schema`
+#[derive(Clone, Debug, PartialEq)]
+pub struct SchemaField {
+    /// Header name, or `column_1`, `column_2`, ... without a header
+    pub name: String,
+    pub data_type: DataType,
+    /// Whether a sampled field was null or missing from a short record
+    pub nullable: bool,
+    /// The first distinct non-null values, unquoted
+    pub examples: Vec<String>,
+}
+
+/// Inferred column types
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Schema {
+    pub fields: Vec<SchemaField>,
+    /// Number of records the types were inferred from
+    pub sampled: usize,
+}
+
+impl Schema {
+    /// Render the schema as a JSON object
+    pub fn to_json(&self) -> String {
+        let mut out = String::new();
+        let _ = write!(out, "{{\"sampled\":{},\"fields\":[", self.sampled);
+        for (i, field) in self.fields.iter().enumerate() {
+            if i > 0 {
+                out.push(',');
+            }
+            out.push_str("{\"name\":");
+            write_json_string(&mut out, &field.name);
+            let _ = write!(
+                out,
+                ",\"type\":\"{}\",\"nullable\":{},\"examples\":[",
+                field.data_type, field.nullable
+            );
+            for (j, example) in field.examples.iter().enumerate() {
+                if j > 0 {
+                    out.push(',');
+                }
+                write_json_string(&mut out, example);
+            }
+            out.push_str("]}");
+        }
+        out.push_str("]}");
+        out
+    }
+}
+
+/// Append `s` as a quoted JSON string
+pub(crate) fn write_json_string(out: &mut String, s: &str) {
+    out.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if (c as u32) < 0x20 => {
+                let _ = write!(out, "\\u{:04x}", c as u32);
+            }
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+}
+
+/// Types a column can still take, narrowed field by field
+#[derive(Clone, Debug)]
+struct Candidates {
+    boolean: bool,
+    int: bool,
+    float: bool,
+    date: bool,
+    timestamp: bool,
+    values: usize,
+    nulls: usize,
+    examples: Vec<String>,
+}
+
+impl Default for Candidates {
+    fn default() -> Self {
+        Self {
+            boolean: true,
+            int: true,
+            float: true,
+            date: true,
+            timestamp: true,
+            values: 0,
+            nulls: 0,
+            examples: Vec::new(),
+        }
+    }
+}
+
+impl Candidates {
+    fn observe(
+        &mut self,
+        field: &[u8],
+        classifier: &Classifier,
+        kernel: DigitsKernel,
+        iso: IsoKernel,
+    ) {
+        if classifier.is_null(field) {
+            self.nulls += 1;
+            return;
+        }
+        self.values += 1;
+        self.boolean = self.boolean && classifier.parse_bool(field).is_ok();
+        self.int = self.int && parse_i64_with(field, kernel).is_ok();
+        self.float = self.float && parse_f64(field).is_ok();
+        self.date = self.date && parse_date_with(field, iso).is_ok();
+        self.timestamp = self.timestamp && parse_timestamp_with(field, iso).is_ok();
+
+        if self.examples.len() < MAX_EXAMPLES {
+            let example = String::from_utf8_lossy(field);
+            if !self.examples.iter().any(|e| *e == example) {
+                self.examples.push(example.into_owned());
+            }
+        }
+    }
+
+    fn data_type(&self) -> DataType {
+        match self {
+            c if c.values == 0 => DataType::Null,
+            c if c.boolean => DataType::Boolean,
+            c if c.int => DataType::Int64,
+            c if c.float => DataType::Float64,
+            c if c.date => DataType::Date,
+            c if c.timestamp => DataType::Timestamp,
+            _ => DataType::Utf8,
+        }
+    }
+}
+
+/// Infer the type of every column from the first `sample` records, or
+/// from all of them with `None`
+///
+/// The header, if `pcsv` has one, names the columns and is not sampled.
+pub fn infer_schema(pcsv: &ParsedCsv, buf: &[u8], sample: Option<usize>) -> Schema {
+    infer_schema_with(pcsv, buf, sample, &ValueConventions::default())
+}
+
+/// Infer the schema, telling nulls and booleans apart with `conventions`
+pub fn infer_schema_with(
+    pcsv: &ParsedCsv,
+    buf: &[u8],
+    sample: Option<usize>,
+    conventions: &ValueConventions,
+) -> Schema {
+    let classifier = Classifier::new(conventions);
+    let kernel = digits_kernel();
+    let iso = iso_kernel();
+    let mut columns: Vec<Candidates> = Vec::new();
+    let mut sampled = 0;
+
+    for record in pcsv.records(buf).take(sample.unwrap_or(usize::MAX)) {
+        if columns.len() < record.len() {
+            // Records before this one lacked the new columns
+            columns.resize_with(record.len(), || Candidates {
+                nulls: sampled,
+                ..Candidates::default()
+            });
+        }
+        for (i, column) in columns.iter_mut().enumerate() {
+            match record.get_unquoted(i) {
+                Some(field) => column.observe(&field, &classifier, kernel, iso),
+                None => column.nulls += 1,
+            }
+        }
+        sampled += 1;
+    }
+
+    let names = pcsv
+        .header_names(buf, &HeaderNormalization::default())
+        .unwrap_or_default();
+    if columns.len() < names.len() {
+        columns.resize_with(names.len(), || Candidates {
+            nulls: sampled,
+            ..Candidates::default()
+        });
+    }
+
+    let fields = columns
+        .into_iter()
+        .enumerate()
+        .map(|(i, column)| SchemaField {
+            name: names
+                .get(i)
+                .cloned()
+                .unwrap_or_else(|| format!("column_{}", i + 1)),
+            data_type: column.data_type(),
+            nullable: column.nulls > 0,
+            examples: column.examples,
+        })
+        .collect();
+    Schema { fields, sampled }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::memory::Padded;
+    use crate::parser::{parse_csv_with, ParseOptions};
+
+    fn parse(data: &[u8], has_header: bool) -> ParsedCsv {
+        let options = ParseOptions {
+            has_header,
+            ..ParseOptions::default()
+        };
+        parse_csv_with(Padded::from_slice(data).as_padded(), &options).unwrap()
+    }
+
+    #[test]
+    fn test_infer_types() {
+        let data = b"b,i,f,d,t,s,n\n\
+            true,1,1.5,2012-08-16,2012-08-16 10:00,x,\n\
+            FALSE,-2,3,2012-08-17,2012-08-17,\"y \"\"z\"\"\",\n\
+            ,3,,2012-08-18,2012-08-18T01:02:03Z,x,\n";
+        let pcsv = parse(data, true);
+        let schema = infer_schema(&pcsv, data, None);
+        assert_eq!(schema.sampled, 3);
+        let types: Vec<DataType> = schema.fields.iter().map(|f| f.data_type).collect();
+        assert_eq!(
+            types,
+            [
+                DataType::Boolean,
+                DataType::Int64,
+                DataType::Float64,
+                DataType::Date,
+                DataType::Timestamp,
+                DataType::Utf8,
+                DataType::Null,
+            ]
+        );
+        let nullable: Vec<bool> = schema.fields.iter().map(|f| f.nullable).collect();
+        assert_eq!(nullable, [true, false, true, false, false, false, true]);
+        assert_eq!(schema.fields[5].name, "s");
+        assert_eq!(schema.fields[5].examples, ["x", "y \"z\""]);
+    }
+
+    #[test]
+    fn test_sample_and_ragged() {
+        let data = b"1,a\n2\n3,b,4.5\nx,c,5\n";
+        let pcsv = parse(data, false);
+
+        let schema = infer_schema(&pcsv, data, Some(3));
+        assert_eq!(schema.sampled, 3);
+        let names: Vec<&str> = schema.fields.iter().map(|f| f.name.as_str()).collect();
+        assert_eq!(names, ["column_1", "column_2", "column_3"]);
+        assert_eq!(schema.fields[0].data_type, DataType::Int64);
+        assert!(schema.fields[1].nullable);
+        assert!(schema.fields[2].nullable);
+        assert_eq!(schema.fields[2].data_type, DataType::Float64);
+
+        let schema = infer_schema(&pcsv, data, None);
+        assert_eq!(schema.fields[0].data_type, DataType::Utf8);
+    }
+
+    #[test]
+    fn test_conventions() {
+        let data = b"1,Y,NA\n\\N,N,NA\n2,Y,\n";
+        let pcsv = parse(data, false);
+        let conventions = ValueConventions {
+            nulls: vec!["NA".to_string(), "\\N".to_string()],
+            trues: vec!["Y".to_string()],
+            falses: vec!["N".to_string()],
+            ..ValueConventions::default()
+        };
+        let types: Vec<DataType> = infer_schema_with(&pcsv, data, None, &conventions)
+            .fields
+            .iter()
+            .map(|f| f.data_type)
+            .collect();
+        assert_eq!(types, [DataType::Int64, DataType::Boolean, DataType::Null]);
+
+        let types: Vec<DataType> = infer_schema(&pcsv, data, None)
+            .fields
+            .iter()
+            .map(|f| f.data_type)
+            .collect();
+        assert_eq!(types, [DataType::Utf8, DataType::Utf8, DataType::Utf8]);
+    }
+
+    #[test]
+    fn test_to_json() {
+        let schema = Schema {
+            fields: vec![SchemaField {
+                name: "a\"b".to_string(),
+                data_type: DataType::Int64,
+                nullable: false,
+                examples: vec!["1\n".to_string()],
+            }],
+            sampled: 1,
+        };
+        assert_eq!(
+            schema.to_json(),
+            r#"{"sampled":1,"fields":[{"name":"a\"b","type":"int64","nullable":false,"examples":["1\n"]}]}"#
+        );
+    }
+}
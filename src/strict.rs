@@ -0,0 +1,277 @@
+//! Strict RFC 4180 TEXTDATA checks for unquoted fields
+//!
+//! RFC 4180 allows only printable ASCII (`%x20-21 / %x23-2B / %x2D-7E`) in
+//! unquoted fields, with CR appearing only as part of a CRLF record
+//! terminator. The checker reuses the quote mask and line-feed mask the
+//! separator scan computes for each 64-byte block, so it adds a few compares
+//! per block and only touches memory when something is wrong.
+
+#[cfg(any(target_arch = "x86_64", target_arch = "aarch64"))]
+use crate::parser::{cmp_mask_against_input, SimdInput};
+use crate::portability::trailing_zeros;
+
+#[cfg(target_arch = "x86_64")]
+use std::arch::x86_64::*;
+
+#[cfg(target_arch = "aarch64")]
+use std::arch::aarch64::*;
+
+/// What is wrong with a byte outside quoted fields
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ViolationKind {
+    /// A byte of 0x80 or above
+    NonAscii,
+    /// A control byte other than the LF and CRLF record terminators
+    ControlByte,
+    /// A CR not followed by LF
+    BareCr,
+}
+
+/// A byte of an unquoted field that RFC 4180 does not allow
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Violation {
+    pub offset: usize,
+    pub kind: ViolationKind,
+}
+
+/// Block-at-a-time TEXTDATA checker
+#[derive(Default)]
+pub(crate) struct TextDataChecker {
+    /// Bit 0 set when the last byte of the previous block was an unquoted CR
+    prev_cr: u64,
+}
+
+/// Masks of bytes at or above 0x80 and bytes below 0x20
+#[cfg(target_arch = "x86_64")]
+#[inline(always)]
+unsafe fn non_ascii_and_control(input: SimdInput) -> (u64, u64) {
+    let non_ascii = (_mm256_movemask_epi8(input.lo) as u32 as u64)
+        | ((_mm256_movemask_epi8(input.hi) as u32 as u64) << 32);
+
+    // Signed compare: also true for bytes >= 0x80, which are removed below
+    let space = _mm256_set1_epi8(0x20);
+    let below_lo = _mm256_movemask_epi8(_mm256_cmpgt_epi8(space, input.lo)) as u32 as u64;
+    let below_hi = _mm256_movemask_epi8(_mm256_cmpgt_epi8(space, input.hi)) as u32 as u64;
+    let control = (below_lo | (below_hi << 32)) & !non_ascii;
+
+    (non_ascii, control)
+}
+
+#[cfg(target_arch = "aarch64")]
+#[inline(always)]
+unsafe fn non_ascii_and_control(input: SimdInput) -> (u64, u64) {
+    use crate::parser::neon_movemask_bulk;
+
+    let high = vdupq_n_u8(0x80);
+    let non_ascii = neon_movemask_bulk(
+        vcgeq_u8(input.i0, high),
+        vcgeq_u8(input.i1, high),
+        vcgeq_u8(input.i2, high),
+        vcgeq_u8(input.i3, high),
+    );
+    let space = vdupq_n_u8(0x20);
+    let control = neon_movemask_bulk(
+        vcltq_u8(input.i0, space),
+        vcltq_u8(input.i1, space),
+        vcltq_u8(input.i2, space),
+        vcltq_u8(input.i3, space),
+    );
+
+    (non_ascii, control)
+}
+
+#[inline(always)]
+fn push_violations(out: &mut Vec<Violation>, base: usize, mut bits: u64, kind: ViolationKind) {
+    while bits != 0 {
+        out.push(Violation {
+            offset: base.wrapping_add(trailing_zeros(bits) as usize),
+            kind,
+        });
+        bits &= bits - 1;
+    }
+}
+
+impl TextDataChecker {
+    /// Check one 64-byte block starting at `idx`
+    ///
+    /// `quote_mask` and `end` are the masks the separator scan computed for
+    /// the block; `len` bounds the data so padding is never reported.
+    #[cfg(any(target_arch = "x86_64", target_arch = "aarch64"))]
+    #[inline(always)]
+    pub(crate) unsafe fn check_block(
+        &mut self,
+        input: SimdInput,
+        idx: usize,
+        len: usize,
+        quote_mask: u64,
+        end: u64,
+        out: &mut Vec<Violation>,
+    ) {
+        let valid = if len - idx < 64 {
+            (1u64 << (len - idx)) - 1
+        } else {
+            !0
+        };
+        let outside = !quote_mask & valid;
+
+        let (non_ascii, below_space) = non_ascii_and_control(input);
+        let cr = cmp_mask_against_input(input, b'\r');
+        let del = cmp_mask_against_input(input, 0x7F);
+        let control = ((below_space & !end & !cr) | del) & outside;
+
+        // Mark the byte after each unquoted CR; the pair may straddle blocks
+        let cr_outside = cr & outside;
+        let after_cr = (cr_outside << 1) | self.prev_cr;
+        self.prev_cr = cr_outside >> 63;
+        let bare = after_cr & !end;
+
+        if (non_ascii & outside) | control | bare == 0 {
+            return;
+        }
+        push_violations(out, idx, non_ascii & outside, ViolationKind::NonAscii);
+        push_violations(out, idx, control, ViolationKind::ControlByte);
+        // Report the CR itself, one byte before the marked position. Bit 0 is
+        // only set by a carry from the previous block, never at offset 0
+        push_violations(out, idx.wrapping_sub(1), bare, ViolationKind::BareCr);
+    }
+
+    /// Report a CR left pending at the very end of the input
+    pub(crate) fn finish(&mut self, len: usize, out: &mut Vec<Violation>) {
+        if self.prev_cr != 0 {
+            out.push(Violation {
+                offset: len - 1,
+                kind: ViolationKind::BareCr,
+            });
+        }
+        self.prev_cr = 0;
+        out.sort_by_key(|v| v.offset);
+    }
+}
+
+/// Scalar TEXTDATA check for targets without a SIMD implementation
+#[cfg_attr(target_arch = "aarch64", allow(dead_code))]
+pub(crate) fn check_textdata_scalar(data: &[u8], out: &mut Vec<Violation>) {
+    let mut in_quote = false;
+    for (i, &byte) in data.iter().enumerate() {
+        if byte == b'"' {
+            in_quote = !in_quote;
+            continue;
+        }
+        if in_quote {
+            continue;
+        }
+        let kind = match byte {
+            b'\n' => continue,
+            b'\r' if data.get(i + 1) == Some(&b'\n') => continue,
+            b'\r' => ViolationKind::BareCr,
+            0x80..=0xFF => ViolationKind::NonAscii,
+            0x00..=0x1F | 0x7F => ViolationKind::ControlByte,
+            _ => continue,
+        };
+        out.push(Violation { offset: i, kind });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::memory::Padded;
+    use crate::parser::{
+        find_indexes_with, parse_csv_with, parse_into_with, ParseOptions, ParsedCsv,
+    };
+
+    fn strict_violations(data: &[u8]) -> Vec<Violation> {
+        let options = ParseOptions {
+            strict: true,
+            ..ParseOptions::default()
+        };
+        parse_csv_with(Padded::from_slice(data).as_padded(), &options)
+            .unwrap()
+            .violations
+    }
+
+    #[test]
+    fn test_strict_accepts_textdata() {
+        let data = "id,name\r\n1,\"a\tb\r\nc \u{e9}\"\r\n2,plain\n".repeat(10);
+        assert!(strict_violations(data.as_bytes()).is_empty());
+    }
+
+    #[test]
+    fn test_strict_reports_offsets() {
+        let violations = strict_violations(b"a,b\tc\rd,\xC3\xA9\n\x7F,\"\x01\"\r");
+        let expected = [
+            (3, ViolationKind::ControlByte),
+            (5, ViolationKind::BareCr),
+            (8, ViolationKind::NonAscii),
+            (9, ViolationKind::NonAscii),
+            (11, ViolationKind::ControlByte),
+            (16, ViolationKind::BareCr),
+        ];
+        let found: Vec<_> = violations.iter().map(|v| (v.offset, v.kind)).collect();
+        assert_eq!(found, expected);
+    }
+
+    #[test]
+    fn test_strict_cr_across_blocks() {
+        // CR as the last byte of a block, followed by LF and by text
+        for (next, bare) in [(b'\n', false), (b'x', true)] {
+            let mut data = vec![b'a'; 63];
+            data.push(b'\r');
+            data.push(next);
+            data.extend_from_slice(&[b'b'; 10]);
+            let violations = strict_violations(&data);
+            assert_eq!(violations.len(), usize::from(bare));
+            if bare {
+                assert_eq!(violations[0].offset, 63);
+            }
+        }
+
+        let mut data = vec![b'a'; 127];
+        data.push(b'\r');
+        assert_eq!(
+            strict_violations(&data),
+            [Violation {
+                offset: 127,
+                kind: ViolationKind::BareCr
+            }]
+        );
+    }
+
+    #[test]
+    fn test_strict_matches_scalar() {
+        let options = ParseOptions {
+            strict: true,
+            ..ParseOptions::default()
+        };
+        let mut data = Vec::new();
+        for i in 0..40u8 {
+            data.extend_from_slice(b"x,\"q\x02\r\",");
+            // Any byte but a quote, so the quoting stays balanced
+            data.push(i.wrapping_mul(37) | 0x01);
+            data.extend_from_slice(if i % 3 == 0 { b"\r\n" } else { b"\n" });
+        }
+
+        for len in [data.len(), data.len() - 1, 255, 256, 257, 63, 64, 65] {
+            let mut expected = Vec::new();
+            check_textdata_scalar(&data[..len], &mut expected);
+            // Truncated inputs may end inside a quote; the violations stay
+            let mut pcsv = ParsedCsv::with_capacity(0);
+            let padded = Padded::from_slice(&data[..len]);
+            let _ = find_indexes_with(padded.as_padded(), &mut pcsv, &options);
+            assert_eq!(pcsv.violations, expected, "length {}", len);
+        }
+    }
+
+    #[test]
+    fn test_parse_into_clears_violations() {
+        let options = ParseOptions {
+            strict: true,
+            ..ParseOptions::default()
+        };
+        let mut pcsv =
+            parse_csv_with(Padded::from_slice(b"a\x00\n").as_padded(), &options).unwrap();
+        assert_eq!(pcsv.violations.len(), 1);
+        parse_into_with(&mut pcsv, Padded::from_slice(b"a\n").as_padded(), &options).unwrap();
+        assert!(pcsv.violations.is_empty());
+    }
+}
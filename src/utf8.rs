@@ -0,0 +1,442 @@
+//! UTF-8 validation using the lookup-table algorithm of simdjson
+//!
+//! Each byte is classified together with the byte before it through three
+//! 16-entry nibble tables; the AND of the three lookups is non-zero exactly
+//! where a two-byte sequence is invalid. Three- and four-byte sequences are
+//! then checked by requiring continuation bytes where a lead byte two or
+//! three positions back asks for them. See Keiser and Lemire, "Validating
+//! UTF-8 In Less Than One Instruction Per Byte" (2021).
+//!
+//! The checker is driven one 64-byte block at a time so the parser can run
+//! it inside its separator scan.
+
+use crate::error::{Error, ParseErrorKind, Result};
+use crate::memory::Padded;
+#[cfg(any(target_arch = "x86_64", target_arch = "aarch64"))]
+use crate::parser::SimdInput;
+
+#[cfg(target_arch = "x86_64")]
+use std::arch::x86_64::*;
+
+#[cfg(target_arch = "aarch64")]
+use std::arch::aarch64::*;
+
+// Error classes, one bit each, as named in simdjson
+const TOO_SHORT: u8 = 1 << 0;
+const TOO_LONG: u8 = 1 << 1;
+const OVERLONG_3: u8 = 1 << 2;
+const TOO_LARGE: u8 = 1 << 3;
+const SURROGATE: u8 = 1 << 4;
+const OVERLONG_2: u8 = 1 << 5;
+const TOO_LARGE_1000: u8 = 1 << 6;
+const OVERLONG_4: u8 = 1 << 6;
+const TWO_CONTS: u8 = 1 << 7;
+const CARRY: u8 = TOO_SHORT | TOO_LONG | TWO_CONTS;
+
+/// Classes by the high nibble of the previous byte
+#[cfg_attr(
+    not(any(target_arch = "x86_64", target_arch = "aarch64")),
+    allow(dead_code)
+)]
+const BYTE_1_HIGH: [u8; 16] = [
+    // 0_______ ASCII
+    TOO_LONG,
+    TOO_LONG,
+    TOO_LONG,
+    TOO_LONG,
+    TOO_LONG,
+    TOO_LONG,
+    TOO_LONG,
+    TOO_LONG,
+    // 10______ continuation
+    TWO_CONTS,
+    TWO_CONTS,
+    TWO_CONTS,
+    TWO_CONTS,
+    // 1100____ two-byte lead
+    TOO_SHORT | OVERLONG_2,
+    // 1101____ two-byte lead
+    TOO_SHORT,
+    // 1110____ three-byte lead
+    TOO_SHORT | OVERLONG_3 | SURROGATE,
+    // 1111____ four-byte lead
+    TOO_SHORT | TOO_LARGE | TOO_LARGE_1000 | OVERLONG_4,
+];
+
+/// Classes by the low nibble of the previous byte
+#[cfg_attr(
+    not(any(target_arch = "x86_64", target_arch = "aarch64")),
+    allow(dead_code)
+)]
+const BYTE_1_LOW: [u8; 16] = [
+    // ____0000
+    CARRY | OVERLONG_3 | OVERLONG_2 | OVERLONG_4,
+    // ____0001
+    CARRY | OVERLONG_2,
+    // ____001_
+    CARRY,
+    CARRY,
+    // ____0100
+    CARRY | TOO_LARGE,
+    // ____0101
+    CARRY | TOO_LARGE | TOO_LARGE_1000,
+    // ____011_
+    CARRY | TOO_LARGE | TOO_LARGE_1000,
+    CARRY | TOO_LARGE | TOO_LARGE_1000,
+    // ____1___
+    CARRY | TOO_LARGE | TOO_LARGE_1000,
+    CARRY | TOO_LARGE | TOO_LARGE_1000,
+    CARRY | TOO_LARGE | TOO_LARGE_1000,
+    CARRY | TOO_LARGE | TOO_LARGE_1000,
+    CARRY | TOO_LARGE | TOO_LARGE_1000,
+    // ____1101
+    CARRY | TOO_LARGE | TOO_LARGE_1000 | SURROGATE,
+    CARRY | TOO_LARGE | TOO_LARGE_1000,
+    CARRY | TOO_LARGE | TOO_LARGE_1000,
+];
+
+/// Classes by the high nibble of the current byte
+#[cfg_attr(
+    not(any(target_arch = "x86_64", target_arch = "aarch64")),
+    allow(dead_code)
+)]
+const BYTE_2_HIGH: [u8; 16] = [
+    // 0_______ ASCII
+    TOO_SHORT,
+    TOO_SHORT,
+    TOO_SHORT,
+    TOO_SHORT,
+    TOO_SHORT,
+    TOO_SHORT,
+    TOO_SHORT,
+    TOO_SHORT,
+    // 1000____
+    TOO_LONG | OVERLONG_2 | TWO_CONTS | OVERLONG_3 | TOO_LARGE_1000 | OVERLONG_4,
+    // 1001____
+    TOO_LONG | OVERLONG_2 | TWO_CONTS | OVERLONG_3 | TOO_LARGE,
+    // 101_____
+    TOO_LONG | OVERLONG_2 | TWO_CONTS | SURROGATE | TOO_LARGE,
+    TOO_LONG | OVERLONG_2 | TWO_CONTS | SURROGATE | TOO_LARGE,
+    // 11______ lead
+    TOO_SHORT,
+    TOO_SHORT,
+    TOO_SHORT,
+    TOO_SHORT,
+];
+
+/// Largest byte values that may end a block without leaving a sequence open
+#[cfg(target_arch = "x86_64")]
+const INCOMPLETE_MAX: [u8; 32] = {
+    let mut max = [0xFF; 32];
+    max[29] = 0xF0 - 1;
+    max[30] = 0xE0 - 1;
+    max[31] = 0xC0 - 1;
+    max
+};
+
+#[cfg(target_arch = "aarch64")]
+const INCOMPLETE_MAX: [u8; 16] = {
+    let mut max = [0xFF; 16];
+    max[13] = 0xF0 - 1;
+    max[14] = 0xE0 - 1;
+    max[15] = 0xC0 - 1;
+    max
+};
+
+/// Streaming UTF-8 checker fed one 64-byte block at a time
+#[cfg(target_arch = "x86_64")]
+pub(crate) struct Utf8Checker {
+    error: __m256i,
+    prev_input: __m256i,
+    prev_incomplete: __m256i,
+}
+
+#[cfg(target_arch = "x86_64")]
+#[inline(always)]
+unsafe fn lookup_16(table: &[u8; 16], idx: __m256i) -> __m256i {
+    let table = _mm256_broadcastsi128_si256(_mm_loadu_si128(table.as_ptr() as *const __m128i));
+    _mm256_shuffle_epi8(table, idx)
+}
+
+#[cfg(target_arch = "x86_64")]
+#[inline(always)]
+unsafe fn high_nibbles(input: __m256i) -> __m256i {
+    _mm256_and_si256(_mm256_srli_epi16(input, 4), _mm256_set1_epi8(0x0F))
+}
+
+/// Concatenate the previous and current chunk and take 32 bytes from `SHIFT` on
+#[cfg(target_arch = "x86_64")]
+#[inline(always)]
+unsafe fn prev<const SHIFT: i32>(input: __m256i, prev_input: __m256i) -> __m256i {
+    _mm256_alignr_epi8(
+        input,
+        _mm256_permute2x128_si256(prev_input, input, 0x21),
+        SHIFT,
+    )
+}
+
+#[cfg(target_arch = "x86_64")]
+impl Utf8Checker {
+    #[inline(always)]
+    pub(crate) unsafe fn new() -> Self {
+        Self {
+            error: _mm256_setzero_si256(),
+            prev_input: _mm256_setzero_si256(),
+            prev_incomplete: _mm256_setzero_si256(),
+        }
+    }
+
+    #[inline(always)]
+    unsafe fn check_chunk(&mut self, input: __m256i) {
+        // _mm256_alignr_epi8 takes the shift from the low end: 16 - N
+        let prev1 = prev::<15>(input, self.prev_input);
+        let prev2 = prev::<14>(input, self.prev_input);
+        let prev3 = prev::<13>(input, self.prev_input);
+
+        let special = _mm256_and_si256(
+            _mm256_and_si256(
+                lookup_16(&BYTE_1_HIGH, high_nibbles(prev1)),
+                lookup_16(&BYTE_1_LOW, _mm256_and_si256(prev1, _mm256_set1_epi8(0x0F))),
+            ),
+            lookup_16(&BYTE_2_HIGH, high_nibbles(input)),
+        );
+
+        // Only 111_____ two back or 1111____ three back stay >= 0x80
+        let is_third = _mm256_subs_epu8(prev2, _mm256_set1_epi8(0xE0u8.wrapping_sub(0x80) as i8));
+        let is_fourth = _mm256_subs_epu8(prev3, _mm256_set1_epi8(0xF0u8.wrapping_sub(0x80) as i8));
+        let must23_80 = _mm256_and_si256(
+            _mm256_or_si256(is_third, is_fourth),
+            _mm256_set1_epi8(0x80u8 as i8),
+        );
+
+        self.error = _mm256_or_si256(self.error, _mm256_xor_si256(must23_80, special));
+        self.prev_input = input;
+    }
+
+    /// Validate one 64-byte block
+    #[inline(always)]
+    pub(crate) unsafe fn check_block(&mut self, input: SimdInput) {
+        let any = _mm256_or_si256(input.lo, input.hi);
+        if _mm256_movemask_epi8(any) == 0 {
+            // An ASCII block cannot finish a sequence left open before it
+            self.error = _mm256_or_si256(self.error, self.prev_incomplete);
+            self.prev_incomplete = _mm256_setzero_si256();
+            self.prev_input = input.hi;
+        } else {
+            self.check_chunk(input.lo);
+            self.check_chunk(input.hi);
+            let max = _mm256_loadu_si256(INCOMPLETE_MAX.as_ptr() as *const __m256i);
+            self.prev_incomplete = _mm256_subs_epu8(input.hi, max);
+        }
+    }
+
+    /// Check the end of input and report whether everything seen was valid
+    #[inline(always)]
+    pub(crate) unsafe fn finish(&mut self) -> bool {
+        self.error = _mm256_or_si256(self.error, self.prev_incomplete);
+        _mm256_testz_si256(self.error, self.error) == 1
+    }
+}
+
+/// Streaming UTF-8 checker fed one 64-byte block at a time
+#[cfg(target_arch = "aarch64")]
+pub(crate) struct Utf8Checker {
+    error: uint8x16_t,
+    prev_input: uint8x16_t,
+    prev_incomplete: uint8x16_t,
+}
+
+#[cfg(target_arch = "aarch64")]
+impl Utf8Checker {
+    #[inline(always)]
+    pub(crate) unsafe fn new() -> Self {
+        Self {
+            error: vdupq_n_u8(0),
+            prev_input: vdupq_n_u8(0),
+            prev_incomplete: vdupq_n_u8(0),
+        }
+    }
+
+    #[inline(always)]
+    unsafe fn check_chunk(&mut self, input: uint8x16_t) {
+        let prev1 = vextq_u8::<15>(self.prev_input, input);
+        let prev2 = vextq_u8::<14>(self.prev_input, input);
+        let prev3 = vextq_u8::<13>(self.prev_input, input);
+
+        let special = vandq_u8(
+            vandq_u8(
+                vqtbl1q_u8(vld1q_u8(BYTE_1_HIGH.as_ptr()), vshrq_n_u8::<4>(prev1)),
+                vqtbl1q_u8(
+                    vld1q_u8(BYTE_1_LOW.as_ptr()),
+                    vandq_u8(prev1, vdupq_n_u8(0x0F)),
+                ),
+            ),
+            vqtbl1q_u8(vld1q_u8(BYTE_2_HIGH.as_ptr()), vshrq_n_u8::<4>(input)),
+        );
+
+        // Only 111_____ two back or 1111____ three back stay >= 0x80
+        let is_third = vqsubq_u8(prev2, vdupq_n_u8(0xE0 - 0x80));
+        let is_fourth = vqsubq_u8(prev3, vdupq_n_u8(0xF0 - 0x80));
+        let must23_80 = vandq_u8(vorrq_u8(is_third, is_fourth), vdupq_n_u8(0x80));
+
+        self.error = vorrq_u8(self.error, veorq_u8(must23_80, special));
+        self.prev_input = input;
+    }
+
+    /// Validate one 64-byte block
+    #[inline(always)]
+    pub(crate) unsafe fn check_block(&mut self, input: SimdInput) {
+        let any = vorrq_u8(vorrq_u8(input.i0, input.i1), vorrq_u8(input.i2, input.i3));
+        if vmaxvq_u8(any) < 0x80 {
+            // An ASCII block cannot finish a sequence left open before it
+            self.error = vorrq_u8(self.error, self.prev_incomplete);
+            self.prev_incomplete = vdupq_n_u8(0);
+            self.prev_input = input.i3;
+        } else {
+            self.check_chunk(input.i0);
+            self.check_chunk(input.i1);
+            self.check_chunk(input.i2);
+            self.check_chunk(input.i3);
+            let max = vld1q_u8(INCOMPLETE_MAX.as_ptr());
+            self.prev_incomplete = vqsubq_u8(input.i3, max);
+        }
+    }
+
+    /// Check the end of input and report whether everything seen was valid
+    #[inline(always)]
+    pub(crate) unsafe fn finish(&mut self) -> bool {
+        self.error = vorrq_u8(self.error, self.prev_incomplete);
+        vmaxvq_u8(self.error) == 0
+    }
+}
+
+/// Build the error for invalid input, locating the first bad byte
+///
+/// The SIMD pass only knows that some block was invalid, so the offset is
+/// recovered with a scalar pass that runs only on failure.
+pub(crate) fn invalid_utf8_error(data: &[u8]) -> Error {
+    let offset = match std::str::from_utf8(data) {
+        Err(e) => e.valid_up_to(),
+        Ok(_) => data.len(),
+    };
+    Error::Parse {
+        offset,
+        kind: ParseErrorKind::InvalidUtf8,
+    }
+}
+
+/// Validate that `buf` is UTF-8 without scanning for separators
+pub fn validate_utf8(buf: Padded<&[u8]>) -> Result<()> {
+    if validate_utf8_impl(buf) {
+        Ok(())
+    } else {
+        Err(invalid_utf8_error(buf.data()))
+    }
+}
+
+#[cfg(target_arch = "x86_64")]
+fn validate_utf8_impl(buf: Padded<&[u8]>) -> bool {
+    if is_x86_feature_detected!("avx2") {
+        unsafe { validate_utf8_avx2(buf) }
+    } else {
+        std::str::from_utf8(buf.data()).is_ok()
+    }
+}
+
+#[cfg(target_arch = "x86_64")]
+#[target_feature(enable = "avx2")]
+unsafe fn validate_utf8_avx2(buf: Padded<&[u8]>) -> bool {
+    let ptr = buf.with_padding().as_ptr();
+    let mut checker = Utf8Checker::new();
+    let mut idx = 0;
+    while idx < buf.len() {
+        checker.check_block(crate::parser::fill_input(ptr.add(idx)));
+        idx += 64;
+    }
+    checker.finish()
+}
+
+#[cfg(target_arch = "aarch64")]
+fn validate_utf8_impl(buf: Padded<&[u8]>) -> bool {
+    let ptr = buf.with_padding().as_ptr();
+    unsafe {
+        let mut checker = Utf8Checker::new();
+        let mut idx = 0;
+        while idx < buf.len() {
+            checker.check_block(crate::parser::fill_input(ptr.add(idx)));
+            idx += 64;
+        }
+        checker.finish()
+    }
+}
+
+#[cfg(not(any(target_arch = "x86_64", target_arch = "aarch64")))]
+fn validate_utf8_impl(buf: Padded<&[u8]>) -> bool {
+    std::str::from_utf8(buf.data()).is_ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn check(data: &[u8]) -> Result<()> {
+        validate_utf8(Padded::from_slice(data).as_padded())
+    }
+
+    #[test]
+    fn test_valid() {
+        assert!(check(b"").is_ok());
+        assert!(check(b"plain,ascii\n").is_ok());
+        let text = "Zürich,€,😀,日本語\n".repeat(20);
+        assert!(check(text.as_bytes()).is_ok());
+    }
+
+    #[test]
+    fn test_invalid_reports_offset() {
+        let cases: &[&[u8]] = &[
+            b"\x80",             // lone continuation
+            b"\xC3",             // truncated two-byte
+            b"\xC0\xAF",         // overlong two-byte
+            b"\xE0\x80\xAF",     // overlong three-byte
+            b"\xED\xA0\x80",     // surrogate
+            b"\xF4\x90\x80\x80", // above U+10FFFF
+            b"\xF8\x88\x80\x80", // five-byte lead
+            b"\xE2\x82",         // truncated three-byte
+        ];
+
+        for case in cases {
+            // Place the bad bytes in the middle of a block and across a block boundary
+            for prefix in [10, 62, 63, 64, 127] {
+                let mut data = vec![b'a'; prefix];
+                data.extend_from_slice(case);
+                data.extend_from_slice(b",tail\n");
+                let err = check(&data).unwrap_err();
+                assert!(
+                    matches!(err, Error::Parse { offset, kind: ParseErrorKind::InvalidUtf8 } if offset == prefix),
+                    "case {:?} at {}: {:?}",
+                    case,
+                    prefix,
+                    err
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_matches_std() {
+        // Every two-byte pattern after an ASCII prefix, against the standard library
+        for a in 0x80..=0xFFu8 {
+            for b in [0x00, 0x41, 0x80, 0x8F, 0x90, 0x9F, 0xA0, 0xBF, 0xC0, 0xF0] {
+                let mut data = vec![b'x'; 61];
+                data.extend_from_slice(&[a, b, 0x80, 0x80]);
+                assert_eq!(
+                    check(&data).is_ok(),
+                    std::str::from_utf8(&data).is_ok(),
+                    "{:02x} {:02x}",
+                    a,
+                    b
+                );
+            }
+        }
+    }
+}
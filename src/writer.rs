@@ -0,0 +1,434 @@
+//! CSV writer
+//!
+//! Fields are scanned for bytes that force quoting 64 bytes at a time, with
+//! the same compare masks the parser uses to find separators.
+
+use crate::column::Column;
+use crate::error::{Error, Result};
+use crate::float::parse_f64;
+use crate::record::Record;
+use std::io::Write;
+
+#[cfg(any(target_arch = "x86_64", target_arch = "aarch64"))]
+use crate::parser::{cmp_mask_against_input, fill_input};
+
+/// Buffered output is handed to the inner writer past this size
+const FLUSH_THRESHOLD: usize = 64 * 1024;
+
+/// When fields are put in quotes
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum QuotePolicy {
+    /// Only fields holding the delimiter, the quote, CR or LF
+    #[default]
+    Minimal,
+    /// Every field
+    Always,
+    /// Every field that is not a number, and fields that need it
+    NonNumeric,
+    /// No field; the output may not parse back if a field needs quotes
+    Never,
+}
+
+/// Line ending written after each record
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Terminator {
+    /// `\n`
+    #[default]
+    Lf,
+    /// `\r\n`, as in RFC 4180
+    CrLf,
+}
+
+impl Terminator {
+    fn as_bytes(self) -> &'static [u8] {
+        match self {
+            Terminator::Lf => b"\n",
+            Terminator::CrLf => b"\r\n",
+        }
+    }
+}
+
+/// The output format of a `CsvWriter`
+///
+/// The parser only reads `,` and `"`; output in other dialects is for other
+/// readers and does not parse back with this crate.
+#[derive(Clone, Copy, Debug)]
+pub struct Dialect {
+    /// Field separator
+    pub delimiter: u8,
+    /// Quote character; quotes inside quoted fields are doubled
+    pub quote: u8,
+    /// Record terminator
+    pub terminator: Terminator,
+    /// Which fields are quoted
+    pub quoting: QuotePolicy,
+}
+
+impl Default for Dialect {
+    fn default() -> Self {
+        Self {
+            delimiter: b',',
+            quote: b'"',
+            terminator: Terminator::Lf,
+            quoting: QuotePolicy::Minimal,
+        }
+    }
+}
+
+impl Dialect {
+    /// Check if output in this dialect parses back to the written fields:
+    /// `,` and `"`, any terminator, and any policy but `Never`
+    pub fn round_trips(&self) -> bool {
+        self.delimiter == b',' && self.quote == b'"' && self.quoting != QuotePolicy::Never
+    }
+}
+
+/// Writes records as CSV to an `io::Write`
+///
+/// Output is buffered; call `flush` or `into_inner` when done.
+pub struct CsvWriter<W: Write> {
+    inner: W,
+    dialect: Dialect,
+    buf: Vec<u8>,
+    /// Whether the next field starts a record
+    record_start: bool,
+}
+
+impl<W: Write> CsvWriter<W> {
+    /// Create a writer with the default dialect: `,`, `"`, LF and minimal quoting
+    pub fn new(inner: W) -> Self {
+        Self::with_dialect(inner, Dialect::default())
+    }
+
+    /// Create a writer for `dialect`
+    pub fn with_dialect(inner: W, dialect: Dialect) -> Self {
+        Self {
+            inner,
+            dialect,
+            buf: Vec::with_capacity(FLUSH_THRESHOLD),
+            record_start: true,
+        }
+    }
+
+    /// Get the dialect being written
+    pub fn dialect(&self) -> &Dialect {
+        &self.dialect
+    }
+
+    /// Append one unquoted field to the current record
+    pub fn write_field(&mut self, field: &[u8]) -> Result<()> {
+        if !self.record_start {
+            self.buf.push(self.dialect.delimiter);
+        }
+        self.record_start = false;
+
+        let quote = match self.dialect.quoting {
+            QuotePolicy::Always => true,
+            QuotePolicy::Never => false,
+            QuotePolicy::Minimal => needs_quotes(field, &self.dialect),
+            QuotePolicy::NonNumeric => !is_numeric(field) || needs_quotes(field, &self.dialect),
+        };
+        if quote {
+            let q = self.dialect.quote;
+            self.buf.push(q);
+            let mut parts = field.split(|&b| b == q);
+            if let Some(first) = parts.next() {
+                self.buf.extend_from_slice(first);
+            }
+            for part in parts {
+                self.buf.extend_from_slice(&[q, q]);
+                self.buf.extend_from_slice(part);
+            }
+            self.buf.push(q);
+        } else {
+            self.buf.extend_from_slice(field);
+        }
+        self.flush_if_full()
+    }
+
+    /// End the current record
+    pub fn end_record(&mut self) -> Result<()> {
+        self.buf
+            .extend_from_slice(self.dialect.terminator.as_bytes());
+        self.record_start = true;
+        self.flush_if_full()
+    }
+
+    /// Write `fields` as one record
+    pub fn write_record<I>(&mut self, fields: I) -> Result<()>
+    where
+        I: IntoIterator,
+        I::Item: AsRef<[u8]>,
+    {
+        for field in fields {
+            self.write_field(field.as_ref())?;
+        }
+        self.end_record()
+    }
+
+    /// Write a parsed record, unquoting its raw fields and requoting them for
+    /// this dialect
+    pub fn write_parsed(&mut self, record: &Record) -> Result<()> {
+        for i in 0..record.len() {
+            self.write_field(&record.get_unquoted(i).unwrap_or_default())?;
+        }
+        self.end_record()
+    }
+
+    /// Write `columns` row by row; fields missing from a column are empty
+    pub fn write_columns(&mut self, columns: &[Column]) -> Result<()> {
+        let rows = columns.iter().map(Column::len).max().unwrap_or(0);
+        for row in 0..rows {
+            for column in columns {
+                self.write_field(&column.get_unquoted(row).unwrap_or_default())?;
+            }
+            self.end_record()?;
+        }
+        Ok(())
+    }
+
+    /// Hand buffered output to the inner writer and flush it
+    pub fn flush(&mut self) -> Result<()> {
+        self.write_buffered()?;
+        self.inner
+            .flush()
+            .map_err(|e| Error::io("failed to flush CSV output", e))
+    }
+
+    /// Flush and return the inner writer
+    pub fn into_inner(mut self) -> Result<W> {
+        self.flush()?;
+        Ok(self.inner)
+    }
+
+    fn flush_if_full(&mut self) -> Result<()> {
+        if self.buf.len() >= FLUSH_THRESHOLD {
+            self.write_buffered()?;
+        }
+        Ok(())
+    }
+
+    fn write_buffered(&mut self) -> Result<()> {
+        self.inner
+            .write_all(&self.buf)
+            .map_err(|e| Error::io("failed to write CSV output", e))?;
+        self.buf.clear();
+        Ok(())
+    }
+}
+
+/// Check if a field reads back as a number
+fn is_numeric(field: &[u8]) -> bool {
+    // Leaves out `inf` and `nan`, which the float parser accepts
+    field
+        .last()
+        .is_some_and(|b| b.is_ascii_digit() || *b == b'.')
+        && parse_f64(field).is_ok()
+}
+
+/// Check if `field` holds the delimiter, the quote, CR or LF
+fn needs_quotes(field: &[u8], dialect: &Dialect) -> bool {
+    #[cfg(target_arch = "x86_64")]
+    {
+        if is_x86_feature_detected!("avx2") {
+            return unsafe { needs_quotes_avx2(field, dialect) };
+        }
+    }
+    #[cfg(target_arch = "aarch64")]
+    {
+        return unsafe { needs_quotes_simd(field, dialect) };
+    }
+    #[allow(unreachable_code)]
+    needs_quotes_scalar(field, dialect)
+}
+
+/// # Safety
+///
+/// This function must only be called on x86_64 CPUs that support AVX2.
+#[cfg(target_arch = "x86_64")]
+#[target_feature(enable = "avx2")]
+unsafe fn needs_quotes_avx2(field: &[u8], dialect: &Dialect) -> bool {
+    needs_quotes_simd(field, dialect)
+}
+
+/// Compare 64-byte blocks against each special byte
+///
+/// The last partial block is copied into a zeroed block, so the caller's
+/// slice is never read past its end.
+#[cfg(any(target_arch = "x86_64", target_arch = "aarch64"))]
+#[inline(always)]
+unsafe fn needs_quotes_simd(field: &[u8], dialect: &Dialect) -> bool {
+    let mut blocks = field.chunks_exact(64);
+    for block in &mut blocks {
+        if special_mask(block.as_ptr(), dialect) != 0 {
+            return true;
+        }
+    }
+    let rest = blocks.remainder();
+    if rest.is_empty() {
+        return false;
+    }
+    let mut tail = [0u8; 64];
+    tail[..rest.len()].copy_from_slice(rest);
+    // Zero padding would match a NUL delimiter or quote
+    special_mask(tail.as_ptr(), dialect) & ((1u64 << rest.len()) - 1) != 0
+}
+
+/// Bit mask of the bytes in the 64-byte block at `ptr` that force quoting
+#[cfg(any(target_arch = "x86_64", target_arch = "aarch64"))]
+#[inline(always)]
+unsafe fn special_mask(ptr: *const u8, dialect: &Dialect) -> u64 {
+    let input = fill_input(ptr);
+    cmp_mask_against_input(input, dialect.delimiter)
+        | cmp_mask_against_input(input, dialect.quote)
+        | cmp_mask_against_input(input, b'\r')
+        | cmp_mask_against_input(input, b'\n')
+}
+
+#[cfg_attr(target_arch = "aarch64", allow(dead_code))]
+fn needs_quotes_scalar(field: &[u8], dialect: &Dialect) -> bool {
+    field
+        .iter()
+        .any(|&b| b == dialect.delimiter || b == dialect.quote || b == b'\r' || b == b'\n')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::memory::Padded;
+    use crate::parser::parse_csv;
+
+    fn write(dialect: Dialect, records: &[&[&str]]) -> String {
+        let mut writer = CsvWriter::with_dialect(Vec::new(), dialect);
+        for record in records {
+            writer.write_record(record.iter()).unwrap();
+        }
+        String::from_utf8(writer.into_inner().unwrap()).unwrap()
+    }
+
+    #[test]
+    fn test_quote_policies() {
+        let records: &[&[&str]] = &[&["id", "name", "price"], &["1", "a \"b\", c", "-2.5"]];
+        let policy = |quoting| Dialect {
+            quoting,
+            ..Dialect::default()
+        };
+        assert_eq!(
+            write(policy(QuotePolicy::Minimal), records),
+            "id,name,price\n1,\"a \"\"b\"\", c\",-2.5\n"
+        );
+        assert_eq!(
+            write(policy(QuotePolicy::Always), records),
+            "\"id\",\"name\",\"price\"\n\"1\",\"a \"\"b\"\", c\",\"-2.5\"\n"
+        );
+        assert_eq!(
+            write(policy(QuotePolicy::NonNumeric), records),
+            "\"id\",\"name\",\"price\"\n1,\"a \"\"b\"\", c\",-2.5\n"
+        );
+        assert_eq!(
+            write(policy(QuotePolicy::Never), records),
+            "id,name,price\n1,a \"b\", c,-2.5\n"
+        );
+
+        let tabs = Dialect {
+            delimiter: b'\t',
+            quote: b'\'',
+            terminator: Terminator::CrLf,
+            ..Dialect::default()
+        };
+        assert_eq!(
+            write(tabs, &[&["a,b", "it's", "x\ty"]]),
+            "a,b\t'it''s'\t'x\ty'\r\n"
+        );
+    }
+
+    #[test]
+    fn test_needs_quotes_blocks() {
+        let dialect = Dialect::default();
+        for len in [0, 1, 63, 64, 65, 130] {
+            let field = vec![b'x'; len];
+            assert!(!needs_quotes(&field, &dialect));
+            assert_eq!(
+                needs_quotes(&field, &dialect),
+                needs_quotes_scalar(&field, &dialect)
+            );
+            for at in [0, len / 2, len.saturating_sub(1)] {
+                if at < len {
+                    let mut field = field.clone();
+                    field[at] = b'\n';
+                    assert!(needs_quotes(&field, &dialect), "{} {}", len, at);
+                }
+            }
+        }
+        let nul = Dialect {
+            delimiter: 0,
+            ..Dialect::default()
+        };
+        assert!(!needs_quotes(b"abc", &nul));
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let long = "y".repeat(100);
+        let data = format!(
+            "a,b,c\n\"1,5\",\"say \"\"hi\"\"\",\n\"line\nbreak\",\"cr\r\",{}\n\"\",x,\"{},\"\r\n",
+            long, long
+        );
+        let pcsv = parse_csv(Padded::from_slice(data.as_bytes()).as_padded()).unwrap();
+        let fields = |pcsv: &crate::parser::ParsedCsv, buf: &[u8]| -> Vec<Vec<Vec<u8>>> {
+            pcsv.records(buf)
+                .map(|r| {
+                    (0..r.len())
+                        .map(|i| r.get_unquoted(i).unwrap().into_owned())
+                        .collect()
+                })
+                .collect()
+        };
+
+        for terminator in [Terminator::Lf, Terminator::CrLf] {
+            for quoting in [
+                QuotePolicy::Minimal,
+                QuotePolicy::Always,
+                QuotePolicy::NonNumeric,
+            ] {
+                let dialect = Dialect {
+                    terminator,
+                    quoting,
+                    ..Dialect::default()
+                };
+                assert!(dialect.round_trips());
+                let mut writer = CsvWriter::with_dialect(Vec::new(), dialect);
+                for record in pcsv.records(data.as_bytes()) {
+                    writer.write_parsed(&record).unwrap();
+                }
+                let out = writer.into_inner().unwrap();
+                let again = parse_csv(Padded::from_slice(&out).as_padded()).unwrap();
+                assert_eq!(
+                    fields(&pcsv, data.as_bytes()),
+                    fields(&again, &out),
+                    "{:?}",
+                    dialect
+                );
+            }
+        }
+        let tabs = Dialect {
+            delimiter: b'\t',
+            ..Dialect::default()
+        };
+        assert!(!tabs.round_trips());
+
+        let mut writer = CsvWriter::new(Vec::new());
+        for record in pcsv.records(data.as_bytes()) {
+            writer.write_parsed(&record).unwrap();
+        }
+        let out = writer.into_inner().unwrap();
+
+        let index = pcsv.column_index(data.as_bytes()).unwrap();
+        let columns: Vec<_> = (0..3)
+            .map(|c| index.column(data.as_bytes(), c).unwrap())
+            .collect();
+        let mut writer = CsvWriter::new(Vec::new());
+        writer.write_columns(&columns).unwrap();
+        assert_eq!(writer.into_inner().unwrap(), out);
+    }
+}